@@ -40,7 +40,9 @@ fn handle_part1(input: &str) -> std::io::Result<()> {
     let mut result = 0;
 
     for line in reader.lines() {
-        let mass = u64::from_str_radix(&line?, 10).expect("Cannot parse a line as a valid u64");
+        let mass = line?
+            .parse::<u64>()
+            .expect("Cannot parse a line as a valid u64");
         result += compute_fuel_part1(mass);
     }
 
@@ -54,7 +56,9 @@ fn handle_part2(input: &str) -> std::io::Result<()> {
     let mut result = 0;
 
     for line in reader.lines() {
-        let mass = u64::from_str_radix(&line?, 10).expect("Cannot parse a line as a valid u64");
+        let mass = line?
+            .parse::<u64>()
+            .expect("Cannot parse a line as a valid u64");
         result += compute_fuel_part2(mass);
     }
 
@@ -14,7 +14,7 @@ impl Movement {
     pub fn from_raw(value: &str) -> Movement {
         let direction = value.chars().next().unwrap();
 
-        let value = i64::from_str_radix(&value[1..], 10).unwrap();
+        let value = value[1..].parse::<i64>().unwrap();
 
         match direction {
             'U' => Movement::Up(value),
@@ -56,7 +56,7 @@ impl Position {
     pub fn as_points(&self, base_position: &Position) -> Vec<Position> {
         let mut result = Vec::new();
         let mut final_position = base_position.clone();
-        final_position.merge_positions(&self);
+        final_position.merge_positions(self);
 
         let diff_x = final_position.x - base_position.x;
         let modifier_x = if diff_x < 0 { -1 } else { 1 };
@@ -0,0 +1,64 @@
+//! Compare the interpreter with and without the decoded instruction cache.
+//!
+//! Run with `cargo bench -p intcode`.
+
+use std::time::{Duration, Instant};
+
+use intcode::asm::assemble;
+use intcode::{IntMachine, MachineReturn};
+
+/// Sum of `i * i` for `i` from 0 up to the input, using every addressing mode.
+const PROGRAM: &str = "
+        in [limit]
+        arb #100
+loop:   mul [i], [i], [rb+0]
+        add [sum], [rb+0], [sum]
+        add [i], #1, [i]
+        lt [i], [limit], [rb+1]
+        jt [rb+1], #loop
+        out [sum]
+        hlt
+i:      .data 0
+sum:    .data 0
+limit:  .data 0
+";
+
+const LIMIT: i64 = 1_000_000;
+const RUNS: u32 = 5;
+
+fn run(code: &[i64], cache: bool) -> (Duration, i64) {
+    let mut best = Duration::MAX;
+    let mut result = 0;
+
+    for _ in 0..RUNS {
+        let mut machine = IntMachine::new(code.to_vec());
+        machine.set_instruction_cache(cache);
+
+        let start = Instant::now();
+
+        match machine.run(false, false, vec![LIMIT]) {
+            Ok(MachineReturn::Exit(outputs)) => result = outputs[0],
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        best = best.min(start.elapsed());
+    }
+
+    (best, result)
+}
+
+fn main() {
+    let code = assemble(PROGRAM).expect("benchmark program should assemble");
+
+    let (uncached, expected) = run(&code, false);
+    let (cached, result) = run(&code, true);
+
+    assert_eq!(result, expected);
+
+    println!("without cache: {:?}", uncached);
+    println!("with cache:    {:?}", cached);
+    println!(
+        "speedup:       {:.2}x",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
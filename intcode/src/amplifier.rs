@@ -0,0 +1,290 @@
+//! Chains of amplifiers and the search of their best phase setting.
+
+use crate::machine::{IntMachine, MachineError};
+use crate::network::{Network, NetworkError, NetworkState, NodeId};
+use crate::snapshot::Snapshot;
+use crate::IntMachineType;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// How amplifiers are connected.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AmplifierMode {
+    /// Each amplifier feeds the next one, the signal going through them once.
+    Chain,
+    /// The last amplifier also feeds the first one, until they all halt.
+    FeedbackLoop,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AmplifierError {
+    /// The program failed before asking for its phase.
+    Boot(MachineError),
+    Network(NetworkError),
+    Deadlock(Vec<NodeId>),
+    /// The last amplifier halted without sending any signal.
+    NoSignal,
+}
+
+/// Every permutation of a slice, generated with Heap's algorithm.
+pub struct Permutations<T> {
+    items: Vec<T>,
+    counters: Vec<usize>,
+    index: usize,
+    first: bool,
+}
+
+pub fn permutations<T: Clone>(items: &[T]) -> Permutations<T> {
+    Permutations {
+        items: items.to_vec(),
+        counters: vec![0; items.len()],
+        index: 1,
+        first: true,
+    }
+}
+
+impl<T: Clone> Iterator for Permutations<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        if self.first {
+            self.first = false;
+            return Some(self.items.clone());
+        }
+
+        while self.index < self.items.len() {
+            let index = self.index;
+
+            if self.counters[index] < index {
+                if index % 2 == 0 {
+                    self.items.swap(0, index);
+                } else {
+                    self.items.swap(self.counters[index], index);
+                }
+
+                self.counters[index] += 1;
+                self.index = 1;
+
+                return Some(self.items.clone());
+            }
+
+            self.counters[index] = 0;
+            self.index += 1;
+        }
+
+        None
+    }
+}
+
+/// Run the amplifier program up to the point where it waits for its phase.
+pub fn boot_amplifier(code: Vec<IntMachineType>) -> Result<Snapshot, AmplifierError> {
+    let mut machine = IntMachine::new(code);
+
+    machine
+        .run(false, false, Vec::new())
+        .map_err(AmplifierError::Boot)?;
+
+    Ok(machine.snapshot())
+}
+
+/// Send the signal 0 through one amplifier per phase, returning the last signal output.
+///
+/// Each amplifier runs on its own thread, connected to the others by channels.
+pub fn run_amplifiers(
+    start: &Snapshot,
+    phases: &[IntMachineType],
+    mode: AmplifierMode,
+) -> Result<IntMachineType, AmplifierError> {
+    run_amplifiers_with(start, phases, mode, true)
+}
+
+/// Run the amplifiers with `Network::run`, or `Network::run_threaded` when `threaded` is set.
+fn run_amplifiers_with(
+    start: &Snapshot,
+    phases: &[IntMachineType],
+    mode: AmplifierMode,
+    threaded: bool,
+) -> Result<IntMachineType, AmplifierError> {
+    let mut network = Network::new();
+
+    let nodes = phases
+        .iter()
+        .map(|phase| {
+            let node = network.add_node(IntMachine::from_snapshot(start));
+
+            network.push_input(node, *phase);
+            node
+        })
+        .collect::<Vec<NodeId>>();
+
+    let (first, last) = match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Err(AmplifierError::NoSignal),
+    };
+
+    for pair in nodes.windows(2) {
+        network.connect(pair[0], pair[1]);
+    }
+
+    if mode == AmplifierMode::FeedbackLoop {
+        network.connect(last, first);
+    }
+
+    network.push_input(first, 0);
+
+    let state = match threaded {
+        true => network.run_threaded(),
+        false => network.run(),
+    };
+
+    match state.map_err(AmplifierError::Network)? {
+        NetworkState::Halted => {}
+        NetworkState::Deadlock(nodes) => return Err(AmplifierError::Deadlock(nodes)),
+    }
+
+    network
+        .outputs(last)
+        .last()
+        .copied()
+        .ok_or(AmplifierError::NoSignal)
+}
+
+/// Try every ordering of `phases`, returning the highest signal and the phases producing it.
+///
+/// The first ordering wins when several produce the same signal.
+pub fn find_best_phase_setting(
+    code: &[IntMachineType],
+    phases: &[IntMachineType],
+    mode: AmplifierMode,
+) -> Result<(IntMachineType, Vec<IntMachineType>), AmplifierError> {
+    let start = boot_amplifier(code.to_vec())?;
+    let mut best: Option<(IntMachineType, Vec<IntMachineType>)> = None;
+
+    for setting in permutations(phases) {
+        let signal = run_amplifiers(&start, &setting, mode)?;
+
+        if best
+            .as_ref()
+            .map_or(true, |(best_signal, _)| *best_signal < signal)
+        {
+            best = Some((signal, setting));
+        }
+    }
+
+    best.ok_or(AmplifierError::NoSignal)
+}
+
+/// Same as `find_best_phase_setting`, the orderings being tried across threads.
+///
+/// Ties and errors are resolved in permutation order, so both always report the same result.
+#[cfg(feature = "parallel")]
+pub fn find_best_phase_setting_parallel(
+    code: &[IntMachineType],
+    phases: &[IntMachineType],
+    mode: AmplifierMode,
+) -> Result<(IntMachineType, Vec<IntMachineType>), AmplifierError> {
+    let start = boot_amplifier(code.to_vec())?;
+
+    permutations(phases)
+        .collect::<Vec<Vec<IntMachineType>>>()
+        .into_par_iter()
+        .map(|setting| {
+            // The rayon pool already keeps every core busy, no need for a thread per amplifier.
+            run_amplifiers_with(&start, &setting, mode, false).map(|signal| (signal, setting))
+        })
+        .reduce_with(|first, second| match (first, second) {
+            (Err(error), _) | (Ok(_), Err(error)) => Err(error),
+            (Ok(first), Ok(second)) if first.0 < second.0 => Ok(second),
+            (Ok(first), Ok(_)) => Ok(first),
+        })
+        .unwrap_or(Err(AmplifierError::NoSignal))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{find_best_phase_setting, permutations, AmplifierError, AmplifierMode};
+
+    #[test]
+    pub fn test_permutations() {
+        assert_eq!(permutations::<i64>(&[]).collect::<Vec<_>>(), vec![vec![]]);
+        assert_eq!(permutations(&[1]).collect::<Vec<_>>(), vec![vec![1]]);
+        assert_eq!(
+            permutations(&[1, 2, 3]).collect::<Vec<_>>(),
+            vec![
+                vec![1, 2, 3],
+                vec![2, 1, 3],
+                vec![3, 1, 2],
+                vec![1, 3, 2],
+                vec![2, 3, 1],
+                vec![3, 2, 1]
+            ]
+        );
+
+        let all = permutations(&[0, 1, 2, 3, 4]).collect::<HashSet<_>>();
+        assert_eq!(all.len(), 120);
+    }
+
+    #[test]
+    pub fn test_find_best_phase_setting() {
+        let code = [
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+
+        assert_eq!(
+            find_best_phase_setting(&code, &[0, 1, 2, 3, 4], AmplifierMode::Chain),
+            Ok((43210, vec![4, 3, 2, 1, 0]))
+        );
+
+        let code = [
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+
+        assert_eq!(
+            find_best_phase_setting(&code, &[5, 6, 7, 8, 9], AmplifierMode::FeedbackLoop),
+            Ok((139629729, vec![9, 8, 7, 6, 5]))
+        );
+
+        assert_eq!(
+            find_best_phase_setting(&[99], &[1, 2], AmplifierMode::Chain),
+            Err(AmplifierError::NoSignal)
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    pub fn test_find_best_phase_setting_parallel() {
+        use super::find_best_phase_setting_parallel;
+
+        // Every ordering produces the same signal, the first one has to win.
+        let code = [3, 9, 3, 10, 4, 10, 99, 0, 0, 0];
+
+        for (phases, mode) in &[
+            (vec![0, 1, 2, 3, 4], AmplifierMode::Chain),
+            (vec![5, 6, 7, 8, 9], AmplifierMode::Chain),
+        ] {
+            assert_eq!(
+                find_best_phase_setting_parallel(&code, phases, *mode),
+                find_best_phase_setting(&code, phases, *mode)
+            );
+        }
+
+        let code = [
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let phases = [5, 6, 7, 8, 9];
+
+        assert_eq!(
+            find_best_phase_setting_parallel(&code, &phases, AmplifierMode::FeedbackLoop),
+            find_best_phase_setting(&code, &phases, AmplifierMode::FeedbackLoop)
+        );
+        assert_eq!(
+            find_best_phase_setting_parallel(&[99], &[1, 2], AmplifierMode::Chain),
+            Err(AmplifierError::NoSignal)
+        );
+    }
+}
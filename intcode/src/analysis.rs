@@ -0,0 +1,487 @@
+//! Static control flow analysis of Intcode programs.
+//!
+//! Programs are explored from address 0, following JumpIfTrue and JumpIfFalse when
+//! their target is immediate. Only writes in position mode are seen: a write in
+//! relative mode may still modify the program without being reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::disasm::{decode_at, Line, Operand, Statement};
+use crate::instruction::InstructionType;
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EdgeKind {
+    /// Execution carries on with the next instruction.
+    FallThrough,
+    Jump,
+}
+
+/// Edge from the block starting at `from` to the instruction at `to`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Instructions always executed in sequence, only the first being a jump target.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BasicBlock {
+    pub lines: Vec<Line>,
+}
+
+impl BasicBlock {
+    pub fn start(&self) -> usize {
+        self.lines[0].address
+    }
+
+    /// Address following the last instruction of the block.
+    pub fn end(&self) -> usize {
+        self.lines
+            .last()
+            .map_or(0, |line| line.address + line.size())
+    }
+}
+
+/// Write of an instruction into the code of a reachable instruction.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SelfModifyingWrite {
+    /// Address of the writing instruction.
+    pub writer: usize,
+    pub target: usize,
+    /// Address of the instruction being modified.
+    pub instruction: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ControlFlowGraph {
+    /// Reachable basic blocks by start address.
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    pub edges: Vec<Edge>,
+    /// Address ranges no reachable instruction covers.
+    pub unreachable: Vec<Range<usize>>,
+    /// Reachable addresses not holding a valid instruction.
+    pub invalid_instructions: Vec<usize>,
+    /// Jumps whose target isn't known statically.
+    pub dynamic_jumps: Vec<usize>,
+    pub self_modifying_writes: Vec<SelfModifyingWrite>,
+}
+
+fn instruction_of(line: &Line) -> Option<(InstructionType, &[Operand])> {
+    match &line.statement {
+        Statement::Instruction {
+            instruction,
+            operands,
+        } => Some((*instruction, operands)),
+        Statement::Data(_) => None,
+    }
+}
+
+/// Value of an immediate operand, `None` when its cell may be written at runtime.
+fn constant_operand(
+    line: &Line,
+    index: usize,
+    volatile: &BTreeSet<usize>,
+) -> Option<IntMachineType> {
+    let (_, operands) = instruction_of(line)?;
+
+    match operands.get(index)? {
+        Operand::Immediate(value) if !volatile.contains(&(line.address + index + 1)) => {
+            Some(*value)
+        }
+        _ => None,
+    }
+}
+
+/// Address written by an instruction in position mode.
+fn written_address(line: &Line) -> Option<usize> {
+    let (instruction, operands) = instruction_of(line)?;
+
+    let operand = match instruction {
+        InstructionType::Addition
+        | InstructionType::Multiplication
+        | InstructionType::LessThan
+        | InstructionType::Equals => operands[2],
+        InstructionType::Input => operands[0],
+        _ => return None,
+    };
+
+    match operand {
+        Operand::Position(address) if address >= 0 => Some(address as usize),
+        _ => None,
+    }
+}
+
+/// Successors of an instruction, and whether it may jump somewhere unknown.
+fn successors(
+    line: &Line,
+    code_len: usize,
+    volatile: &BTreeSet<usize>,
+) -> (Vec<(usize, EdgeKind)>, bool) {
+    let next = line.address + line.size();
+    let fall_through = || match next < code_len {
+        true => vec![(next, EdgeKind::FallThrough)],
+        false => Vec::new(),
+    };
+
+    let jump_if_true = match instruction_of(line) {
+        Some((InstructionType::Exit, _)) | None => return (Vec::new(), false),
+        Some((InstructionType::JumpIfTrue, _)) => true,
+        Some((InstructionType::JumpIfFalse, _)) => false,
+        Some(_) => return (fall_through(), false),
+    };
+
+    let jumps = constant_operand(line, 0, volatile).map(|value| (value != 0) == jump_if_true);
+    let mut result = Vec::new();
+
+    let dynamic = match (jumps, constant_operand(line, 1, volatile)) {
+        (Some(false), _) => false,
+        (_, Some(target)) => {
+            if 0 <= target && (target as usize) < code_len {
+                result.push((target as usize, EdgeKind::Jump));
+            }
+
+            false
+        }
+        (_, None) => true,
+    };
+
+    if jumps != Some(true) {
+        result.extend(fall_through());
+    }
+
+    (result, dynamic)
+}
+
+/// Every reachable instruction by address, cells in `volatile` not being trusted as constants.
+fn explore(code: &[IntMachineType], volatile: &BTreeSet<usize>) -> BTreeMap<usize, Line> {
+    let mut reached = BTreeMap::new();
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if reached.contains_key(&address) {
+            continue;
+        }
+
+        let line = match decode_at(code, address) {
+            Some(line) => line,
+            None => continue,
+        };
+
+        pending.extend(
+            successors(&line, code.len(), volatile)
+                .0
+                .iter()
+                .map(|(address, _)| *address),
+        );
+        reached.insert(address, line);
+    }
+
+    reached
+}
+
+/// Whether an instruction is a conditional jump, ending its basic block.
+fn is_conditional_jump(line: &Line) -> bool {
+    matches!(
+        instruction_of(line),
+        Some((InstructionType::JumpIfTrue, _)) | Some((InstructionType::JumpIfFalse, _))
+    )
+}
+
+/// Start address of the reachable instruction covering `address`.
+fn covering_instruction(reached: &BTreeMap<usize, Line>, address: usize) -> Option<usize> {
+    reached
+        .range(..=address)
+        .rev()
+        .find(|(start, line)| address < *start + line.size())
+        .map(|(start, _)| *start)
+}
+
+/// Build the control flow graph of a program.
+pub fn analyze(code: &[IntMachineType]) -> ControlFlowGraph {
+    // Immediate operands overwritten by the program aren't constants, which can make more code reachable.
+    let mut volatile = BTreeSet::new();
+
+    let reached = loop {
+        let reached = explore(code, &volatile);
+        let written = reached
+            .values()
+            .filter_map(written_address)
+            .collect::<BTreeSet<usize>>();
+
+        if written.is_subset(&volatile) {
+            break reached;
+        }
+
+        volatile.extend(written);
+    };
+
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+
+    for line in reached.values() {
+        let (targets, _) = successors(line, code.len(), &volatile);
+
+        for (target, kind) in targets {
+            if kind == EdgeKind::Jump || is_conditional_jump(line) {
+                leaders.insert(target);
+            }
+        }
+    }
+
+    let mut graph = ControlFlowGraph {
+        blocks: BTreeMap::new(),
+        edges: Vec::new(),
+        unreachable: Vec::new(),
+        invalid_instructions: Vec::new(),
+        dynamic_jumps: Vec::new(),
+        self_modifying_writes: Vec::new(),
+    };
+    let mut block: Option<BasicBlock> = None;
+
+    for (address, line) in &reached {
+        if instruction_of(line).is_none() {
+            if let Some(previous) = block.take() {
+                if previous.end() == *address {
+                    graph.edges.push(Edge {
+                        from: previous.start(),
+                        to: *address,
+                        kind: EdgeKind::FallThrough,
+                    });
+                }
+
+                graph.blocks.insert(previous.start(), previous);
+            }
+
+            graph.invalid_instructions.push(*address);
+            continue;
+        }
+
+        let (targets, dynamic) = successors(line, code.len(), &volatile);
+
+        if dynamic {
+            graph.dynamic_jumps.push(*address);
+        }
+
+        let mut current = match block.take() {
+            Some(current) if current.end() == *address && !leaders.contains(address) => current,
+            Some(previous) => {
+                if previous.end() == *address {
+                    graph.edges.push(Edge {
+                        from: previous.start(),
+                        to: *address,
+                        kind: EdgeKind::FallThrough,
+                    });
+                }
+
+                graph.blocks.insert(previous.start(), previous);
+                BasicBlock { lines: Vec::new() }
+            }
+            None => BasicBlock { lines: Vec::new() },
+        };
+
+        current.lines.push(line.clone());
+
+        if is_conditional_jump(line) || dynamic || targets.is_empty() {
+            graph.edges.extend(targets.iter().map(|(to, kind)| Edge {
+                from: current.start(),
+                to: *to,
+                kind: *kind,
+            }));
+            graph.blocks.insert(current.start(), current);
+        } else {
+            block = Some(current);
+        }
+    }
+
+    if let Some(last) = block {
+        graph.blocks.insert(last.start(), last);
+    }
+
+    let mut covered = vec![false; code.len()];
+
+    for (address, line) in &reached {
+        for cell in covered.iter_mut().skip(*address).take(line.size()) {
+            *cell = true;
+        }
+    }
+
+    let mut start = None;
+
+    for (address, covered) in covered.iter().chain(&[true]).enumerate() {
+        match (start, covered) {
+            (None, false) => start = Some(address),
+            (Some(range_start), true) => {
+                graph.unreachable.push(range_start..address);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    for (address, line) in &reached {
+        let target = match written_address(line) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        if let Some(instruction) = covering_instruction(&reached, target) {
+            graph.self_modifying_writes.push(SelfModifyingWrite {
+                writer: *address,
+                target,
+                instruction,
+            });
+        }
+    }
+
+    graph
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl ControlFlowGraph {
+    /// Format the graph for Graphviz, invalid instructions being drawn as octagons.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec![
+            "digraph intcode {".to_string(),
+            "    node [shape=box, fontname=\"monospace\"];".to_string(),
+        ];
+
+        for (start, block) in &self.blocks {
+            let label = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", escape(line.to_string().trim_start())))
+                .collect::<String>();
+
+            lines.push(format!("    block_{} [label=\"{}\"];", start, label));
+        }
+
+        for address in &self.invalid_instructions {
+            lines.push(format!(
+                "    block_{} [label=\"{}: invalid\", shape=octagon];",
+                address, address
+            ));
+        }
+
+        for edge in &self.edges {
+            let label = match edge.kind {
+                EdgeKind::FallThrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+            };
+
+            lines.push(format!(
+                "    block_{} -> block_{}{};",
+                edge.from, edge.to, label
+            ));
+        }
+
+        lines.push("}".to_string());
+
+        let mut result = lines.join("\n");
+        result.push('\n');
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{analyze, Edge, EdgeKind, SelfModifyingWrite};
+
+    fn block_ranges(code: &[i64]) -> Vec<(usize, usize)> {
+        analyze(code)
+            .blocks
+            .values()
+            .map(|block| (block.start(), block.end()))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_blocks() {
+        // The input overwrites the condition of the jump, which can go both ways.
+        let code = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        let graph = analyze(&code);
+
+        assert_eq!(block_ranges(&code), vec![(0, 5), (5, 9), (9, 12)]);
+        assert_eq!(
+            graph.edges,
+            vec![
+                Edge {
+                    from: 0,
+                    to: 9,
+                    kind: EdgeKind::Jump
+                },
+                Edge {
+                    from: 0,
+                    to: 5,
+                    kind: EdgeKind::FallThrough
+                },
+                Edge {
+                    from: 5,
+                    to: 9,
+                    kind: EdgeKind::FallThrough
+                },
+            ]
+        );
+        assert_eq!(graph.unreachable, vec![12..13]);
+        assert_eq!(
+            graph.self_modifying_writes,
+            vec![SelfModifyingWrite {
+                writer: 0,
+                target: 3,
+                instruction: 2
+            }]
+        );
+
+        let graph = analyze(&[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9]);
+        assert_eq!(graph.dynamic_jumps, vec![2]);
+        assert_eq!(graph.unreachable, vec![12..16]);
+    }
+
+    #[test]
+    pub fn test_unreachable_and_invalid() {
+        let code = [1105, 1, 7, 99, 99, 99, 99, 4, 0, 99];
+        let graph = analyze(&code);
+
+        assert_eq!(block_ranges(&code), vec![(0, 3), (7, 10)]);
+        assert_eq!(graph.unreachable, vec![3..7]);
+        assert_eq!(graph.edges.len(), 1);
+
+        let graph = analyze(&[1106, 0, 4, 99, 42]);
+        assert_eq!(graph.invalid_instructions, vec![4]);
+        assert_eq!(graph.unreachable, vec![3..4]);
+        assert!(graph.self_modifying_writes.is_empty());
+
+        let graph = analyze(&[1101, 1, 1, 0, 42]);
+        assert_eq!(graph.invalid_instructions, vec![4]);
+        assert_eq!(
+            graph.edges,
+            vec![Edge {
+                from: 0,
+                to: 4,
+                kind: EdgeKind::FallThrough
+            }]
+        );
+
+        // Writing 99 over the opcode of the next instruction.
+        let graph = analyze(&[1101, 99, 0, 4, 1, 0, 0, 0]);
+        assert_eq!(graph.self_modifying_writes[0].instruction, 4);
+    }
+
+    #[test]
+    pub fn test_dot() {
+        let dot = analyze(&[1106, 0, 5, 104, 1, 99]).to_dot();
+
+        assert_eq!(
+            dot,
+            "digraph intcode {\n    node [shape=box, fontname=\"monospace\"];\n    \
+             block_0 [label=\"0: jf #0, #5\\l\"];\n    \
+             block_5 [label=\"5: hlt\\l\"];\n    \
+             block_0 -> block_5 [label=\"jump\"];\n}\n"
+        );
+    }
+}
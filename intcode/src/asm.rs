@@ -0,0 +1,421 @@
+//! Assembler for a small Intcode assembly language.
+//!
+//! Each line holds optional labels followed by an instruction or a `.data`
+//! directive, comments start with `;`:
+//!
+//! ```text
+//! start:  in [value]
+//!         mul [value], #3, [value]
+//!         out [value]
+//!         jt #1, #start
+//! value:  .data 0
+//! ```
+//!
+//! Operands are `#value` (immediate), `[address]` (position) or `[rb+offset]`
+//! (relative), where values may be numbers, labels or `label+offset`.
+//! Numeric labels such as `12:` are ignored so disassembler listings can be
+//! assembled back.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::instruction::{InstructionType, ParameterMode};
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, message: String) -> Self {
+        AsmError { line, message }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+enum ParsedStatement<'a> {
+    Instruction(InstructionType, Vec<&'a str>),
+    Data(Vec<&'a str>),
+}
+
+impl<'a> ParsedStatement<'a> {
+    fn size(&self) -> usize {
+        match self {
+            ParsedStatement::Instruction(instruction, _) => instruction.code_size(),
+            ParsedStatement::Data(values) => values.len(),
+        }
+    }
+}
+
+struct SourceLine<'a> {
+    line: usize,
+    statement: ParsedStatement<'a>,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    let text = text.trim();
+
+    if text.is_empty() {
+        return Vec::new();
+    }
+
+    text.split(',').map(str::trim).collect()
+}
+
+fn parse_statement(line: usize, text: &str) -> Result<ParsedStatement<'_>, AsmError> {
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], &text[index..]),
+        None => (text, ""),
+    };
+
+    let operands = split_operands(rest);
+
+    if mnemonic == ".data" {
+        if operands.is_empty() {
+            return Err(AsmError::new(line, "empty .data directive".to_string()));
+        }
+
+        return Ok(ParsedStatement::Data(operands));
+    }
+
+    let instruction = InstructionType::from_mnemonic(mnemonic)
+        .ok_or_else(|| AsmError::new(line, format!("unknown mnemonic `{}`", mnemonic)))?;
+
+    if operands.len() != instruction.arguments_count() {
+        return Err(AsmError::new(
+            line,
+            format!(
+                "`{}` expects {} operands, got {}",
+                mnemonic,
+                instruction.arguments_count(),
+                operands.len()
+            ),
+        ));
+    }
+
+    Ok(ParsedStatement::Instruction(instruction, operands))
+}
+
+fn resolve_value(
+    line: usize,
+    text: &str,
+    labels: &HashMap<&str, usize>,
+) -> Result<IntMachineType, AsmError> {
+    let text = text.trim();
+
+    if let Ok(value) = text.parse::<IntMachineType>() {
+        return Ok(value);
+    }
+
+    if text.is_empty() {
+        return Err(AsmError::new(line, "missing value".to_string()));
+    }
+
+    let separator = text
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '+' || *c == '-');
+
+    let (name, offset) = match separator {
+        Some((index, _)) => {
+            let (name, offset) = text.split_at(index);
+            let offset = offset
+                .replace(' ', "")
+                .trim_start_matches('+')
+                .parse::<IntMachineType>()
+                .map_err(|_| AsmError::new(line, format!("invalid offset in `{}`", text)))?;
+
+            (name.trim(), offset)
+        }
+        None => (text, 0),
+    };
+
+    let address = match labels.get(name) {
+        Some(address) => *address as IntMachineType,
+        None => return Err(AsmError::new(line, format!("unknown label `{}`", name))),
+    };
+
+    address
+        .checked_add(offset)
+        .ok_or_else(|| AsmError::new(line, format!("`{}` overflows", text)))
+}
+
+fn resolve_operand(
+    line: usize,
+    text: &str,
+    labels: &HashMap<&str, usize>,
+) -> Result<(ParameterMode, IntMachineType), AsmError> {
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok((
+            ParameterMode::Immediate,
+            resolve_value(line, value, labels)?,
+        ));
+    }
+
+    let inner = match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(inner) => inner.trim(),
+        None => return Err(AsmError::new(line, format!("invalid operand `{}`", text))),
+    };
+
+    // Labels may start with `rb` too, only `rb`, `rb+...` and `rb-...` are relative.
+    let relative = inner
+        .strip_prefix("rb")
+        .map(str::trim)
+        .filter(|offset| offset.is_empty() || offset.starts_with(['+', '-']));
+
+    if let Some(offset) = relative {
+        let value = if let Some(offset) = offset.strip_prefix('+') {
+            resolve_value(line, offset, labels)?
+        } else if let Some(offset) = offset.strip_prefix('-') {
+            resolve_value(line, offset, labels)?
+                .checked_neg()
+                .ok_or_else(|| AsmError::new(line, format!("`{}` overflows", text)))?
+        } else {
+            0
+        };
+
+        return Ok((ParameterMode::Relative, value));
+    }
+
+    Ok((ParameterMode::Position, resolve_value(line, inner, labels)?))
+}
+
+/// Assemble `source` into a program ready to be loaded by an `IntMachine`.
+pub fn assemble(source: &str) -> Result<Vec<IntMachineType>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut text = raw_line.split(';').next().unwrap_or("").trim();
+
+        while let Some(index) = text.find(':') {
+            let label = text[..index].trim();
+
+            if label.chars().all(|c| c.is_ascii_digit()) && !label.is_empty() {
+                // Address column of a disassembler listing.
+            } else if is_identifier(label) && label != "rb" {
+                if labels.insert(label, address).is_some() {
+                    return Err(AsmError::new(line, format!("duplicate label `{}`", label)));
+                }
+            } else {
+                break;
+            }
+
+            text = text[index + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line, text)?;
+
+        address += statement.size();
+        statements.push(SourceLine { line, statement });
+    }
+
+    let mut result = Vec::with_capacity(address);
+
+    for source_line in statements {
+        let line = source_line.line;
+
+        match source_line.statement {
+            ParsedStatement::Instruction(instruction, operands) => {
+                let mut opcode = instruction.opcode();
+                let mut values = Vec::new();
+
+                for (i, operand) in operands.iter().enumerate() {
+                    let (parameter_mode, value) = resolve_operand(line, operand, &labels)?;
+
+                    opcode += parameter_mode.to_opcode(i);
+                    values.push(value);
+                }
+
+                result.push(opcode as IntMachineType);
+                result.extend(values);
+            }
+            ParsedStatement::Data(values) => {
+                for value in values {
+                    result.push(resolve_value(line, value, &labels)?);
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Format a program in the comma separated format read by `read_code`.
+pub fn format_code(code: &[IntMachineType]) -> String {
+    code.iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assemble, format_code, AsmError};
+    use crate::disasm::disassemble;
+    use crate::{IntMachine, MachineReturn};
+
+    #[test]
+    pub fn test_assemble() {
+        assert_eq!(
+            assemble("mul [4], #3, [4]\n.data 33"),
+            Ok(vec![1002, 4, 3, 4, 33])
+        );
+        assert_eq!(
+            assemble(
+                "
+                ; compare the input with 8
+                        in [value]
+                        eq [value], [eight], [value]
+                        out [value]
+                        hlt
+                value:  .data -1
+                eight:  .data 8
+                "
+            ),
+            Ok(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8])
+        );
+        assert_eq!(
+            assemble("arb #19\nout [rb-34]\nadd [rb], [rb+2], [rb + 3]"),
+            Ok(vec![109, 19, 204, -34, 22201, 0, 2, 3])
+        );
+        assert_eq!(
+            assemble("start: jt #1, #end+1\nend: .data start, end, end-1"),
+            Ok(vec![1105, 1, 4, 0, 3, 2])
+        );
+    }
+
+    #[test]
+    pub fn test_labels_starting_with_rb() {
+        assert_eq!(assemble("rbuf: .data 5\nout [rbuf]"), Ok(vec![5, 4, 0]));
+        assert_eq!(
+            assemble("out [rb+rbuf]\nout [rb - rbuf]\nrbuf: .data 5"),
+            Ok(vec![204, 4, 204, -4, 5])
+        );
+        assert_eq!(
+            assemble("out [rbx]"),
+            Err(AsmError {
+                line: 1,
+                message: "unknown label `rbx`".to_string()
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_run_assembled() {
+        let code = assemble(
+            "
+            loop:   in [value]
+                    jf [value], #done
+                    mul [value], #2, [value]
+                    out [value]
+                    jt #1, #loop
+            done:   hlt
+            value:  .data 0
+            ",
+        )
+        .unwrap();
+
+        let mut machine = IntMachine::new(code);
+
+        assert_eq!(
+            machine.run(false, false, vec![1, 21, -4, 0]),
+            Ok(MachineReturn::Exit(vec![2, 42, -8]))
+        );
+    }
+
+    #[test]
+    pub fn test_disassembly_round_trip() {
+        let code = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+
+        let listing = disassemble(&code)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        assert_eq!(assemble(&listing), Ok(code));
+    }
+
+    #[test]
+    pub fn test_errors() {
+        assert_eq!(
+            assemble("hlt\nfoo #1"),
+            Err(AsmError {
+                line: 2,
+                message: "unknown mnemonic `foo`".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("add #1, #2"),
+            Err(AsmError {
+                line: 1,
+                message: "`add` expects 3 operands, got 2".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("jt #1, #nowhere"),
+            Err(AsmError {
+                line: 1,
+                message: "unknown label `nowhere`".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("a: hlt\na: hlt"),
+            Err(AsmError {
+                line: 2,
+                message: "duplicate label `a`".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("out [rb--9223372036854775808]"),
+            Err(AsmError {
+                line: 1,
+                message: "`[rb--9223372036854775808]` overflows".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("hlt\na: out [a+9223372036854775807]"),
+            Err(AsmError {
+                line: 2,
+                message: "`a+9223372036854775807` overflows".to_string()
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_format_code() {
+        assert_eq!(format_code(&[1002, 4, 3, 4, -33]), "1002,4,3,4,-33");
+    }
+}
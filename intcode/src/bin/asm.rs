@@ -0,0 +1,22 @@
+use std::env;
+use std::fs;
+
+use intcode::asm::{assemble, format_code};
+
+fn main() -> std::io::Result<()> {
+    let input_path = env::args()
+        .nth(1)
+        .expect("Please provide a file as argument");
+
+    let source = fs::read_to_string(&input_path)?;
+
+    match assemble(&source) {
+        Ok(code) => println!("{}", format_code(&code)),
+        Err(error) => {
+            eprintln!("{}: {}", input_path, error);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+
+use intcode::debugger::Debugger;
+use intcode::{read_code, IntMachine, IntMachineType};
+
+fn main() -> std::io::Result<()> {
+    let input_path = env::args()
+        .nth(1)
+        .expect("Please provide a file as argument");
+
+    let code = read_code(&input_path)?;
+    let mut debugger = Debugger::new(IntMachine::new(code));
+
+    for value in env::args().skip(2) {
+        debugger.push_input(
+            value
+                .parse::<IntMachineType>()
+                .expect("input should be a number"),
+        );
+    }
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut line = String::new();
+
+    loop {
+        write!(stdout, "(debug) ")?;
+        stdout.flush()?;
+
+        line.clear();
+
+        if stdin.lock().read_line(&mut line)? == 0 || !debugger.execute(&line, &mut stdout)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use std::env;
+
+use intcode::disasm::disassemble;
+use intcode::read_code;
+
+fn main() -> std::io::Result<()> {
+    let input_path = env::args()
+        .nth(1)
+        .expect("Please provide a file as argument");
+
+    let code = read_code(&input_path)?;
+
+    for line in disassemble(&code) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
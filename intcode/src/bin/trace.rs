@@ -0,0 +1,44 @@
+use std::env;
+use std::io;
+
+use intcode::trace::{JsonLinesTracer, TextTracer, Tracer};
+use intcode::{read_code, IntMachine, IntMachineType};
+
+fn main() -> std::io::Result<()> {
+    let format = env::args()
+        .nth(1)
+        .expect("Please provide a format (json or text)");
+
+    let input_path = env::args()
+        .nth(2)
+        .expect("Please provide a file as argument");
+
+    let mut input_values = Vec::new();
+
+    for value in env::args().skip(3) {
+        input_values.push(
+            value
+                .parse::<IntMachineType>()
+                .expect("input should be a number"),
+        );
+    }
+
+    let tracer: Box<dyn Tracer + Send> = match format.as_str() {
+        "json" => Box::new(JsonLinesTracer::new(io::stdout())),
+        "text" => Box::new(TextTracer::new(io::stdout())),
+        _ => {
+            eprintln!("unknown format `{}`, expected json or text", format);
+            std::process::exit(1);
+        }
+    };
+
+    let mut machine = IntMachine::new(read_code(&input_path)?);
+
+    machine.set_tracer(Some(tracer));
+
+    let result = machine.run(false, false, input_values);
+
+    eprintln!("{:?}", result);
+
+    Ok(())
+}
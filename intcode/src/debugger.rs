@@ -0,0 +1,450 @@
+//! Interactive step debugger around an `IntMachine`.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::{self, Write};
+
+use crate::disasm::decode_at;
+use crate::machine::{IntMachine, StepResult};
+use crate::snapshot::Snapshot;
+use crate::IntMachineType;
+
+const HELP: &str = "\
+commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint, a watchpoint, input starvation or exit
+  b, break <ip>        set a breakpoint on ip
+  d, delete <ip>       remove a breakpoint
+  w, watch <addr>      stop when the value at addr changes
+  u, unwatch <addr>    remove a watchpoint
+  i, input <values>    queue input values
+  r, regs              print registers
+  l, list [n]          disassemble n instructions from ip (default 5)
+  m, mem <addr> [n]    dump n words of memory from addr (default 8)
+  bookmark [name]      save the machine state as name, list bookmarks without name
+  restore <name>       bring the machine back to a bookmarked state
+  q, quit              leave the debugger";
+
+/// Longest instruction in words, used to size disassembly windows.
+const MAX_INSTRUCTION_SIZE: usize = 4;
+
+pub struct Debugger {
+    machine: IntMachine,
+    input: VecDeque<IntMachineType>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, IntMachineType>,
+    bookmarks: BTreeMap<String, Snapshot>,
+    steps: u64,
+    halted: bool,
+}
+
+enum StopReason {
+    Running,
+    Breakpoint,
+    Watchpoint,
+    NeedsInput,
+    Exit,
+    Error,
+}
+
+fn parse_arguments<T: std::str::FromStr>(arguments: &[&str]) -> Option<Vec<T>> {
+    arguments
+        .iter()
+        .map(|argument| argument.parse().ok())
+        .collect()
+}
+
+impl Debugger {
+    pub fn new(machine: IntMachine) -> Self {
+        Debugger {
+            machine,
+            input: VecDeque::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            bookmarks: BTreeMap::new(),
+            steps: 0,
+            halted: false,
+        }
+    }
+
+    pub fn machine(&self) -> &IntMachine {
+        &self.machine
+    }
+
+    pub fn push_input(&mut self, value: IntMachineType) {
+        self.input.push_back(value);
+    }
+
+    fn step(&mut self, out: &mut dyn Write) -> io::Result<StopReason> {
+        if self.halted {
+            writeln!(out, "machine halted")?;
+            return Ok(StopReason::Exit);
+        }
+
+        let result = self.machine.step_with(&mut self.input, &mut ());
+
+        let mut reason = match result {
+            Ok(StepResult::Continue) => StopReason::Running,
+            Ok(StepResult::Output(value)) => {
+                writeln!(out, "output: {}", value)?;
+                StopReason::Running
+            }
+            Ok(StepResult::NeedsInput) => {
+                writeln!(out, "waiting for input at ip {}", self.machine.ip())?;
+                return Ok(StopReason::NeedsInput);
+            }
+            Ok(StepResult::Exit) => {
+                self.halted = true;
+                writeln!(out, "machine halted at ip {}", self.machine.ip())?;
+                return Ok(StopReason::Exit);
+            }
+            Err(error) => {
+                writeln!(out, "error: {:?}", error)?;
+                return Ok(StopReason::Error);
+            }
+        };
+
+        self.steps += 1;
+
+        for (address, last_value) in self.watchpoints.iter_mut() {
+            let value = self.machine.memory().read(*address).unwrap_or(0);
+
+            if value != *last_value {
+                writeln!(out, "watchpoint [{}]: {} -> {}", address, last_value, value)?;
+                *last_value = value;
+                reason = StopReason::Watchpoint;
+            }
+        }
+
+        if let StopReason::Running = reason {
+            if self.breakpoints.contains(&self.machine.ip()) {
+                writeln!(out, "breakpoint at ip {}", self.machine.ip())?;
+                reason = StopReason::Breakpoint;
+            }
+        }
+
+        Ok(reason)
+    }
+
+    fn list(&self, count: usize, out: &mut dyn Write) -> io::Result<()> {
+        let ip = self.machine.ip();
+        let memory = self.machine.memory();
+
+        // Every instruction takes at least one word, the listing stops at the end of memory.
+        let count = count.min(memory.len().saturating_sub(ip).max(1));
+        let window = (ip..ip.saturating_add(count * MAX_INSTRUCTION_SIZE))
+            .map(|address| memory.read(address).unwrap_or(0))
+            .collect::<Vec<IntMachineType>>();
+
+        let mut offset = 0;
+
+        for index in 0..count {
+            if index > 0 && ip + offset >= memory.len() {
+                break;
+            }
+
+            let mut line = match decode_at(&window, offset) {
+                Some(line) => line,
+                // No word to decode as ip is at the very end of the address space.
+                None if offset == 0 => return writeln!(out, "=> {:>5}: <ip out of range>", ip),
+                None => break,
+            };
+            let marker = if offset == 0 { "=>" } else { "  " };
+
+            offset += line.size();
+            line.address += ip;
+
+            writeln!(out, "{} {}", marker, line)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_registers(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "ip: {}  rb: {}  steps: {}",
+            self.machine.ip(),
+            self.machine.relative_base(),
+            self.steps
+        )?;
+        writeln!(out, "input: {:?}", self.input)?;
+        writeln!(out, "outputs: {:?}", self.machine.output_values())
+    }
+
+    /// Execute one command line, returning `false` once the user asked to quit.
+    pub fn execute(&mut self, command_line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words = command_line.split_whitespace().collect::<Vec<&str>>();
+
+        let (command, arguments) = match words.split_first() {
+            Some((command, arguments)) => (*command, arguments),
+            None => return Ok(true),
+        };
+
+        match command {
+            "s" | "step" => {
+                let count = match parse_arguments::<u64>(arguments) {
+                    Some(values) if values.len() <= 1 => values.first().copied().unwrap_or(1),
+                    _ => return writeln!(out, "usage: step [n]").map(|_| true),
+                };
+
+                for _ in 0..count {
+                    match self.step(out)? {
+                        StopReason::Running | StopReason::Breakpoint => {}
+                        _ => break,
+                    }
+                }
+
+                self.list(1, out)?;
+            }
+            "c" | "continue" => {
+                while let StopReason::Running = self.step(out)? {}
+
+                self.list(1, out)?;
+            }
+            "b" | "break" | "d" | "delete" | "w" | "watch" | "u" | "unwatch" => {
+                let address = match parse_arguments::<usize>(arguments) {
+                    Some(values) if values.len() == 1 => values[0],
+                    _ => return writeln!(out, "usage: {} <address>", command).map(|_| true),
+                };
+
+                match command {
+                    "b" | "break" => {
+                        self.breakpoints.insert(address);
+                    }
+                    "d" | "delete" => {
+                        self.breakpoints.remove(&address);
+                    }
+                    "w" | "watch" => {
+                        let value = self.machine.memory().read(address).unwrap_or(0);
+                        self.watchpoints.insert(address, value);
+                    }
+                    _ => {
+                        self.watchpoints.remove(&address);
+                    }
+                }
+
+                writeln!(out, "breakpoints: {:?}", self.breakpoints)?;
+                writeln!(
+                    out,
+                    "watchpoints: {:?}",
+                    self.watchpoints.keys().collect::<Vec<&usize>>()
+                )?;
+            }
+            "i" | "input" => match parse_arguments::<IntMachineType>(arguments) {
+                Some(values) if !values.is_empty() => {
+                    self.input.extend(values);
+                    writeln!(out, "input: {:?}", self.input)?;
+                }
+                _ => writeln!(out, "usage: input <values>")?,
+            },
+            "r" | "regs" => self.print_registers(out)?,
+            "l" | "list" => match parse_arguments::<usize>(arguments) {
+                Some(values) if values.len() <= 1 => {
+                    self.list(values.first().copied().unwrap_or(5), out)?
+                }
+                _ => writeln!(out, "usage: list [n]")?,
+            },
+            "m" | "mem" => match parse_arguments::<usize>(arguments) {
+                Some(values) if !values.is_empty() && values.len() <= 2 => {
+                    let start = values[0];
+                    let count = values.get(1).copied().unwrap_or(8);
+
+                    let end = match start.checked_add(count) {
+                        // The dump stops at the end of memory, past the first word.
+                        Some(end) => end.min(self.machine.memory().len().max(start + 1)),
+                        None => return writeln!(out, "usage: mem <address> [n]").map(|_| true),
+                    };
+
+                    for address in start..end {
+                        match self.machine.memory().read(address) {
+                            Ok(value) => writeln!(out, "[{}] {}", address, value)?,
+                            Err(error) => {
+                                writeln!(out, "[{}] {:?}", address, error)?;
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ => writeln!(out, "usage: mem <address> [n]")?,
+            },
+            "bookmark" => match arguments {
+                [] => writeln!(
+                    out,
+                    "bookmarks: {:?}",
+                    self.bookmarks.keys().collect::<Vec<&String>>()
+                )?,
+                [name] => {
+                    self.bookmarks
+                        .insert(name.to_string(), self.machine.snapshot());
+                    writeln!(out, "bookmarked `{}` at ip {}", name, self.machine.ip())?;
+                }
+                _ => writeln!(out, "usage: bookmark [name]")?,
+            },
+            "restore" => match arguments {
+                [name] => match self.bookmarks.get(*name) {
+                    Some(snapshot) => {
+                        self.machine.restore(snapshot);
+                        self.steps = snapshot.steps;
+                        self.halted = false;
+
+                        for (address, value) in self.watchpoints.iter_mut() {
+                            *value = self.machine.memory().read(*address).unwrap_or(0);
+                        }
+
+                        self.list(1, out)?;
+                    }
+                    None => writeln!(out, "no bookmark named `{}`", name)?,
+                },
+                _ => writeln!(out, "usage: restore <name>")?,
+            },
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "unknown command `{}`, try `help`", command)?,
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Debugger;
+    use crate::IntMachine;
+
+    fn execute(debugger: &mut Debugger, command: &str) -> String {
+        let mut out = Vec::new();
+
+        debugger.execute(command, &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    pub fn test_step_and_list() {
+        let mut debugger = Debugger::new(IntMachine::new(vec![1002, 4, 3, 4, 33]));
+
+        assert_eq!(
+            execute(&mut debugger, "list 1"),
+            "=>     0: mul [4], #3, [4]\n"
+        );
+        assert_eq!(execute(&mut debugger, "step"), "=>     4: hlt\n");
+        assert_eq!(
+            execute(&mut debugger, "s"),
+            "machine halted at ip 4\n=>     4: hlt\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "regs"),
+            "ip: 4  rb: 0  steps: 1\ninput: []\noutputs: []\n"
+        );
+    }
+
+    #[test]
+    pub fn test_breakpoints_and_input() {
+        let code = vec![3, 12, 1001, 12, 1, 12, 4, 12, 1105, 1, 0, 99];
+        let mut debugger = Debugger::new(IntMachine::new(code));
+
+        execute(&mut debugger, "break 6");
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "waiting for input at ip 0\n=>     0: in [12]\n"
+        );
+
+        execute(&mut debugger, "input 41 9");
+        assert_eq!(
+            execute(&mut debugger, "c"),
+            "breakpoint at ip 6\n=>     6: out [12]\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "c"),
+            "output: 42\nbreakpoint at ip 6\n=>     6: out [12]\n"
+        );
+        assert_eq!(debugger.machine().output_values(), &[42]);
+    }
+
+    #[test]
+    pub fn test_watchpoints() {
+        let code = vec![1101, 1, 2, 9, 1101, 3, 4, 10, 99];
+        let mut debugger = Debugger::new(IntMachine::new(code));
+
+        execute(&mut debugger, "watch 10");
+        assert_eq!(
+            execute(&mut debugger, "c"),
+            "watchpoint [10]: 0 -> 7\n=>     8: hlt\n"
+        );
+        assert_eq!(execute(&mut debugger, "mem 9 2"), "[9] 3\n[10] 7\n");
+        assert_eq!(execute(&mut debugger, "break"), "usage: break <address>\n");
+    }
+
+    #[test]
+    pub fn test_large_windows() {
+        let mut debugger = Debugger::new(IntMachine::new(vec![1002, 4, 3, 4, 33]));
+
+        assert_eq!(
+            execute(&mut debugger, "list 5000000000000000000"),
+            "=>     0: mul [4], #3, [4]\n       4: .data 33\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "mem 3 18446744073709551612"),
+            "[3] 4\n[4] 33\n"
+        );
+        assert_eq!(execute(&mut debugger, "mem 7"), "[7] 0\n");
+        assert_eq!(
+            execute(&mut debugger, "mem 18446744073709551615 8"),
+            "usage: mem <address> [n]\n"
+        );
+    }
+
+    #[test]
+    pub fn test_ip_out_of_range() {
+        let mut debugger = Debugger::new(IntMachine::new(vec![1105, 1, -1]));
+
+        assert_eq!(
+            execute(&mut debugger, "step"),
+            "error: NegativeAddress(-1)\n=>     3: .data 0\n"
+        );
+
+        let mut snapshot = IntMachine::new(vec![99]).snapshot();
+        snapshot.ip = usize::MAX;
+
+        let mut debugger = Debugger::new(IntMachine::from_snapshot(&snapshot));
+
+        assert_eq!(
+            execute(&mut debugger, "list"),
+            format!("=> {}: <ip out of range>\n", usize::MAX)
+        );
+    }
+
+    #[test]
+    pub fn test_bookmarks() {
+        let code = vec![3, 12, 1001, 12, 1, 12, 4, 12, 1105, 1, 0, 99];
+        let mut debugger = Debugger::new(IntMachine::new(code));
+
+        execute(&mut debugger, "input 1 2");
+        execute(&mut debugger, "step 2");
+        assert_eq!(
+            execute(&mut debugger, "bookmark first"),
+            "bookmarked `first` at ip 6\n"
+        );
+
+        execute(&mut debugger, "step 5");
+        assert_eq!(debugger.machine().output_values(), &[2, 3]);
+
+        assert_eq!(
+            execute(&mut debugger, "restore first"),
+            "=>     6: out [12]\n"
+        );
+        assert_eq!(debugger.machine().output_values(), &[] as &[i64]);
+        assert_eq!(
+            execute(&mut debugger, "regs"),
+            "ip: 6  rb: 0  steps: 2\ninput: []\noutputs: []\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "restore second"),
+            "no bookmark named `second`\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "bookmark"),
+            "bookmarks: [\"first\"]\n"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt;
+
+use crate::instruction::{InstructionType, ParameterMode};
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Operand<W = IntMachineType> {
+    Position(W),
+    Immediate(W),
+    Relative(W),
+}
+
+impl<W> Operand<W> {
+    pub fn new(parameter_mode: ParameterMode, value: W) -> Self {
+        match parameter_mode {
+            ParameterMode::Position => Operand::Position(value),
+            ParameterMode::Immediate => Operand::Immediate(value),
+            ParameterMode::Relative => Operand::Relative(value),
+        }
+    }
+}
+
+impl<W: fmt::Display> fmt::Display for Operand<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Position(address) => write!(f, "[{}]", address),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Relative(offset) => {
+                let offset = offset.to_string();
+
+                match offset.strip_prefix('-') {
+                    Some(magnitude) => write!(f, "[rb-{}]", magnitude),
+                    None => write!(f, "[rb+{}]", offset),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Statement {
+    Instruction {
+        instruction: InstructionType,
+        operands: Vec<Operand>,
+    },
+    Data(IntMachineType),
+}
+
+/// A single decoded statement of a program and the address it starts at.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Line {
+    pub address: usize,
+    pub statement: Statement,
+}
+
+impl Line {
+    pub fn size(&self) -> usize {
+        match &self.statement {
+            Statement::Instruction { instruction, .. } => instruction.code_size(),
+            Statement::Data(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5}: ", self.address)?;
+
+        match &self.statement {
+            Statement::Instruction {
+                instruction,
+                operands,
+            } => {
+                write!(f, "{}", instruction.mnemonic())?;
+
+                for (i, operand) in operands.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+
+                    write!(f, "{}{}", separator, operand)?;
+                }
+
+                Ok(())
+            }
+            Statement::Data(value) => write!(f, ".data {}", value),
+        }
+    }
+}
+
+/// Decode the statement at `address`, falling back to `.data` when it isn't a valid instruction.
+///
+/// Return `None` when `address` is past the end of `code`.
+pub fn decode_at(code: &[IntMachineType], address: usize) -> Option<Line> {
+    let opcode = *code.get(address)?;
+
+    let data = Line {
+        address,
+        statement: Statement::Data(opcode),
+    };
+
+    if opcode < 0 {
+        return Some(data);
+    }
+
+    let instruction = match InstructionType::from_opcode(opcode as usize) {
+        Ok(instruction) => instruction,
+        Err(_) => return Some(data),
+    };
+
+    let parameter_modes = match instruction.arguments_configuration(opcode as usize) {
+        Ok(parameter_modes) => parameter_modes,
+        Err(_) => return Some(data),
+    };
+
+    if code.len() < address + instruction.code_size() {
+        return Some(data);
+    }
+
+    let operands = parameter_modes
+        .iter()
+        .enumerate()
+        .map(|(i, parameter_mode)| Operand::new(*parameter_mode, code[address + i + 1]))
+        .collect();
+
+    Some(Line {
+        address,
+        statement: Statement::Instruction {
+            instruction,
+            operands,
+        },
+    })
+}
+
+/// Linearly decode a whole program.
+pub fn disassemble(code: &[IntMachineType]) -> Vec<Line> {
+    let mut result = Vec::new();
+    let mut address = 0;
+
+    while let Some(line) = decode_at(code, address) {
+        address += line.size();
+        result.push(line);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_at, disassemble, Line, Operand, Statement};
+    use crate::InstructionType;
+
+    fn listing(code: &[i64]) -> Vec<String> {
+        disassemble(code).iter().map(Line::to_string).collect()
+    }
+
+    #[test]
+    pub fn test_disassemble() {
+        assert_eq!(
+            listing(&[1002, 4, 3, 4, 33]),
+            vec!["    0: mul [4], #3, [4]", "    4: .data 33"]
+        );
+        assert_eq!(
+            listing(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]),
+            vec![
+                "    0: in [9]",
+                "    2: eq [9], [10], [9]",
+                "    6: out [9]",
+                "    8: hlt",
+                "    9: .data -1",
+                "   10: .data 8",
+            ]
+        );
+        assert_eq!(
+            listing(&[109, 19, 204, -34, 1105, 1, 0, 22201, 1, 2, 3]),
+            vec![
+                "    0: arb #19",
+                "    2: out [rb-34]",
+                "    4: jt #1, #0",
+                "    7: add [rb+1], [rb+2], [rb+3]",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_decode_at() {
+        let code = [1002, 4, 3, 4, 33];
+
+        assert_eq!(
+            decode_at(&code, 4),
+            Some(Line {
+                address: 4,
+                statement: Statement::Data(33)
+            })
+        );
+        assert_eq!(decode_at(&code, 5), None);
+        assert_eq!(decode_at(&[], 0), None);
+        assert_eq!(decode_at(&code, usize::MAX), None);
+    }
+
+    #[test]
+    pub fn test_invalid_as_data() {
+        let lines = disassemble(&[301, 0, 0, 0, 1, 0, 0]);
+
+        assert_eq!(lines[0].statement, Statement::Data(301));
+        assert_eq!(lines[4].statement, Statement::Data(1));
+        assert_eq!(lines.len(), 7);
+
+        assert_eq!(
+            disassemble(&[1101, 1, 2, 0])[0].statement,
+            Statement::Instruction {
+                instruction: InstructionType::Addition,
+                operands: vec![
+                    Operand::Immediate(1),
+                    Operand::Immediate(2),
+                    Operand::Position(0)
+                ],
+            }
+        );
+    }
+}
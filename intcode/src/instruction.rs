@@ -0,0 +1,280 @@
+use crate::machine::{IntMachine, MachineError};
+use crate::word::Word;
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl ParameterMode {
+    pub fn from_opcode(opcode: usize, position: usize) -> Result<Self, MachineError> {
+        let raw_mode = (opcode / usize::pow(10, (position + 2) as u32)) % 10;
+
+        match raw_mode {
+            0 => Ok(ParameterMode::Position),
+            1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
+            _ => Err(MachineError::InvalidParameterMode(opcode)),
+        }
+    }
+
+    /// Encode the mode as the digit used in the opcode for the argument at `position`.
+    pub fn to_opcode(self, position: usize) -> usize {
+        let raw_mode = match self {
+            ParameterMode::Position => 0,
+            ParameterMode::Immediate => 1,
+            ParameterMode::Relative => 2,
+        };
+
+        raw_mode * usize::pow(10, (position + 2) as u32)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstructionArgument<W = IntMachineType> {
+    pub value: W,
+    pub parameter_mode: ParameterMode,
+    pub argument_position: usize,
+}
+
+pub(crate) fn to_address<W: Word>(value: &W) -> Result<usize, MachineError> {
+    if value.is_negative() {
+        return Err(MachineError::NegativeAddress(
+            value.to_i64().unwrap_or(i64::MIN),
+        ));
+    }
+
+    value.to_usize().ok_or(MachineError::OutOfBound(usize::MAX))
+}
+
+impl<W: Word> InstructionArgument<W> {
+    /// Address of the memory cell the argument refers to.
+    ///
+    /// Immediate arguments refer to their own position in the instruction.
+    pub fn address(&self, machine: &IntMachine<W>) -> Result<usize, MachineError> {
+        match self.parameter_mode {
+            ParameterMode::Immediate => Ok(self.argument_position),
+            ParameterMode::Position => to_address(&self.value),
+            ParameterMode::Relative => match machine.relative_base.checked_add(&self.value) {
+                Some(address) => to_address(&address),
+                None => Err(MachineError::OutOfBound(usize::MAX)),
+            },
+        }
+    }
+
+    pub fn get_value(&self, machine: &IntMachine<W>) -> Result<W, MachineError> {
+        match self.parameter_mode {
+            ParameterMode::Immediate => Ok(self.value.clone()),
+            _ => machine.read_at_position(self.address(machine)?),
+        }
+    }
+
+    pub fn write_value(&self, machine: &mut IntMachine<W>, value: W) -> Result<(), MachineError> {
+        let address = self.address(machine)?;
+
+        machine.write_at_position(address, value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum InstructionType {
+    Addition,
+    Multiplication,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Exit,
+}
+
+impl InstructionType {
+    pub fn from_opcode(opcode: usize) -> Result<InstructionType, MachineError> {
+        match opcode % 100 {
+            1 => Ok(InstructionType::Addition),
+            2 => Ok(InstructionType::Multiplication),
+            3 => Ok(InstructionType::Input),
+            4 => Ok(InstructionType::Output),
+            5 => Ok(InstructionType::JumpIfTrue),
+            6 => Ok(InstructionType::JumpIfFalse),
+            7 => Ok(InstructionType::LessThan),
+            8 => Ok(InstructionType::Equals),
+            9 => Ok(InstructionType::AdjustRelativeBase),
+            99 => Ok(InstructionType::Exit),
+            _ => Err(MachineError::InvalidInstruction(opcode)),
+        }
+    }
+
+    pub fn opcode(self) -> usize {
+        match self {
+            InstructionType::Addition => 1,
+            InstructionType::Multiplication => 2,
+            InstructionType::Input => 3,
+            InstructionType::Output => 4,
+            InstructionType::JumpIfTrue => 5,
+            InstructionType::JumpIfFalse => 6,
+            InstructionType::LessThan => 7,
+            InstructionType::Equals => 8,
+            InstructionType::AdjustRelativeBase => 9,
+            InstructionType::Exit => 99,
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<InstructionType> {
+        match mnemonic {
+            "add" => Some(InstructionType::Addition),
+            "mul" => Some(InstructionType::Multiplication),
+            "in" => Some(InstructionType::Input),
+            "out" => Some(InstructionType::Output),
+            "jt" => Some(InstructionType::JumpIfTrue),
+            "jf" => Some(InstructionType::JumpIfFalse),
+            "lt" => Some(InstructionType::LessThan),
+            "eq" => Some(InstructionType::Equals),
+            "arb" => Some(InstructionType::AdjustRelativeBase),
+            "hlt" => Some(InstructionType::Exit),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            InstructionType::Addition => "add",
+            InstructionType::Multiplication => "mul",
+            InstructionType::Input => "in",
+            InstructionType::Output => "out",
+            InstructionType::JumpIfTrue => "jt",
+            InstructionType::JumpIfFalse => "jf",
+            InstructionType::LessThan => "lt",
+            InstructionType::Equals => "eq",
+            InstructionType::AdjustRelativeBase => "arb",
+            InstructionType::Exit => "hlt",
+        }
+    }
+
+    pub fn arguments_count(self) -> usize {
+        match self {
+            InstructionType::Addition => 3,
+            InstructionType::Multiplication => 3,
+            InstructionType::Input => 1,
+            InstructionType::Output => 1,
+            InstructionType::JumpIfTrue => 2,
+            InstructionType::JumpIfFalse => 2,
+            InstructionType::LessThan => 3,
+            InstructionType::Equals => 3,
+            InstructionType::AdjustRelativeBase => 1,
+            InstructionType::Exit => 0,
+        }
+    }
+
+    pub fn arguments_configuration(
+        self,
+        opcode: usize,
+    ) -> Result<Vec<ParameterMode>, MachineError> {
+        let mut result = Vec::new();
+
+        for i in 0..self.arguments_count() {
+            result.push(ParameterMode::from_opcode(opcode, i)?)
+        }
+
+        Ok(result)
+    }
+
+    pub fn code_size(self) -> usize {
+        self.arguments_count() + 1
+    }
+
+    pub fn read_instruction<W: Word>(
+        self,
+        machine: &IntMachine<W>,
+    ) -> Result<InstructionContext<W>, MachineError> {
+        let mut arguments = Vec::new();
+
+        for (i, parameter_mode) in self
+            .arguments_configuration(machine.read_opcode()?)?
+            .iter()
+            .enumerate()
+        {
+            arguments.push(InstructionArgument {
+                value: machine.read_at_position(machine.ip + i + 1)?,
+                parameter_mode: *parameter_mode,
+                argument_position: machine.ip + i + 1,
+            });
+        }
+
+        Ok(InstructionContext {
+            instruction: self,
+            arguments,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct InstructionContext<W = IntMachineType> {
+    pub instruction: InstructionType,
+    pub arguments: Vec<InstructionArgument<W>>,
+}
+
+/// Largest number of arguments taken by an instruction.
+pub(crate) const MAX_ARGUMENTS: usize = 3;
+
+/// Instruction decoded into a fixed-size form, cheap to cache and to clone.
+#[derive(Debug, Clone)]
+pub(crate) struct DecodedInstruction<W = IntMachineType> {
+    pub(crate) instruction: InstructionType,
+    arguments: [InstructionArgument<W>; MAX_ARGUMENTS],
+}
+
+impl<W: Word> DecodedInstruction<W> {
+    /// Decode the instruction at the machine `ip`.
+    pub(crate) fn decode(machine: &IntMachine<W>) -> Result<Self, MachineError> {
+        let opcode = machine.read_opcode()?;
+        let instruction = InstructionType::from_opcode(opcode)?;
+
+        let mut arguments = std::array::from_fn(|_| InstructionArgument {
+            value: W::zero(),
+            parameter_mode: ParameterMode::Immediate,
+            argument_position: 0,
+        });
+
+        for (i, argument) in arguments
+            .iter_mut()
+            .enumerate()
+            .take(instruction.arguments_count())
+        {
+            argument.parameter_mode = ParameterMode::from_opcode(opcode, i)?;
+            argument.argument_position = machine.ip + i + 1;
+            argument.value = machine.read_at_position(argument.argument_position)?;
+        }
+
+        Ok(DecodedInstruction {
+            instruction,
+            arguments,
+        })
+    }
+
+    pub(crate) fn arguments(&self) -> &[InstructionArgument<W>] {
+        &self.arguments[..self.instruction.arguments_count()]
+    }
+}
+
+impl<W: Word> From<InstructionContext<W>> for DecodedInstruction<W> {
+    fn from(context: InstructionContext<W>) -> Self {
+        let mut arguments = context.arguments.into_iter();
+
+        DecodedInstruction {
+            instruction: context.instruction,
+            arguments: std::array::from_fn(|_| {
+                arguments.next().unwrap_or(InstructionArgument {
+                    value: W::zero(),
+                    parameter_mode: ParameterMode::Immediate,
+                    argument_position: 0,
+                })
+            }),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Stdin, Stdout, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::machine::MachineError;
+use crate::word::Word;
+use crate::IntMachineType;
+
+/// Source of values consumed by the Input instruction.
+pub trait Input<W = IntMachineType> {
+    /// Return the next value, or `None` when nothing is available.
+    fn read(&mut self) -> Result<Option<W>, MachineError>;
+}
+
+/// Sink of values produced by the Output instruction.
+pub trait Output<W = IntMachineType> {
+    fn write(&mut self, value: W) -> Result<(), MachineError>;
+}
+
+impl<W> Input<W> for VecDeque<W> {
+    fn read(&mut self) -> Result<Option<W>, MachineError> {
+        Ok(self.pop_front())
+    }
+}
+
+impl<W, F> Input<W> for F
+where
+    F: FnMut() -> Option<W>,
+{
+    fn read(&mut self) -> Result<Option<W>, MachineError> {
+        Ok(self())
+    }
+}
+
+/// Block until a value is received, the sender hanging up meaning no more input.
+impl<W> Input<W> for Receiver<W> {
+    fn read(&mut self) -> Result<Option<W>, MachineError> {
+        Ok(self.recv().ok())
+    }
+}
+
+/// Read one value per line, skipping blank lines.
+impl<W: Word> Input<W> for Stdin {
+    fn read(&mut self) -> Result<Option<W>, MachineError> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            let read_size = self
+                .lock()
+                .read_line(&mut line)
+                .map_err(|error| MachineError::InvalidInput(error.to_string()))?;
+
+            if read_size == 0 {
+                return Ok(None);
+            }
+
+            let value = line.trim();
+
+            if !value.is_empty() {
+                return value
+                    .parse::<W>()
+                    .map(Some)
+                    .map_err(|_| MachineError::InvalidInput(value.to_string()));
+            }
+        }
+    }
+}
+
+/// Discard every value.
+impl<W> Output<W> for () {
+    fn write(&mut self, _value: W) -> Result<(), MachineError> {
+        Ok(())
+    }
+}
+
+impl<W> Output<W> for Vec<W> {
+    fn write(&mut self, value: W) -> Result<(), MachineError> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl<W> Output<W> for VecDeque<W> {
+    fn write(&mut self, value: W) -> Result<(), MachineError> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl<W, F> Output<W> for F
+where
+    F: FnMut(W),
+{
+    fn write(&mut self, value: W) -> Result<(), MachineError> {
+        self(value);
+        Ok(())
+    }
+}
+
+impl<W> Output<W> for Sender<W> {
+    fn write(&mut self, value: W) -> Result<(), MachineError> {
+        self.send(value).map_err(|_| MachineError::OutputClosed)
+    }
+}
+
+/// Print one value per line.
+impl<W: Word> Output<W> for Stdout {
+    fn write(&mut self, value: W) -> Result<(), MachineError> {
+        writeln!(self, "{}", value).map_err(|_| MachineError::OutputClosed)
+    }
+}
@@ -0,0 +1,40 @@
+//! Shared Intcode virtual machine used by the Intcode puzzles.
+
+use std::fs;
+
+pub mod amplifier;
+pub mod analysis;
+pub mod asm;
+pub mod debugger;
+pub mod disasm;
+mod instruction;
+mod io;
+mod machine;
+mod memory;
+pub mod network;
+mod parse;
+pub mod profile;
+pub mod self_modification;
+pub mod snapshot;
+pub mod solver;
+pub mod symbolic;
+pub mod trace;
+mod word;
+
+pub use instruction::{InstructionArgument, InstructionContext, InstructionType, ParameterMode};
+pub use io::{Input, Output};
+pub use machine::{IntMachine, MachineError, MachineReturn, OverflowPolicy, StepResult};
+pub use memory::{Memory, DEFAULT_MEMORY_LIMIT, PAGE_SIZE};
+pub use parse::{parse_code, parse_words, ParseError};
+pub use word::{convert_code, Word};
+
+pub type IntMachineType = i64;
+
+/// Read a comma separated Intcode program from `input_file`.
+///
+/// Invalid programs are reported as `InvalidData` errors wrapping a `ParseError`.
+pub fn read_code(input_file: &str) -> std::io::Result<Vec<IntMachineType>> {
+    let text = fs::read_to_string(input_file)?;
+
+    parse_code(&text).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+}
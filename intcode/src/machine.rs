@@ -0,0 +1,1107 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::disasm::Operand;
+use crate::instruction::{
+    to_address, DecodedInstruction, InstructionArgument, InstructionType, ParameterMode,
+    MAX_ARGUMENTS,
+};
+use crate::io::{Input, Output};
+use crate::memory::Memory;
+use crate::snapshot::Snapshot;
+use crate::trace::{MemoryRead, MemoryWrite, TraceEvent, Tracer};
+use crate::word::Word;
+use crate::IntMachineType;
+
+/// Number of addresses, starting at 0, whose decoded instruction can be cached.
+const INSTRUCTION_CACHE_SIZE: usize = 1 << 16;
+
+/// Number of steps between two checks of the clock when a timeout is set.
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+pub struct IntMachine<W = IntMachineType> {
+    pub(crate) ip: usize,
+    pub(crate) relative_base: W,
+    ram: Memory<W>,
+    output_values: Vec<W>,
+    steps: u64,
+    overflow_policy: OverflowPolicy,
+    step_budget: Option<u64>,
+    timeout: Option<Duration>,
+    /// Decoded instructions by address, entries being dropped when their code is written to.
+    instruction_cache: Vec<Option<DecodedInstruction<W>>>,
+    instruction_cache_enabled: bool,
+    tracer: Option<Box<dyn Tracer<W> + Send>>,
+    trace_event: Option<TraceEvent<W>>,
+}
+
+/// What the ALU does when an addition or a multiplication doesn't fit in a word.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum OverflowPolicy {
+    /// Stop the machine with `MachineError::Overflow`.
+    #[default]
+    Error,
+    Wrapping,
+    Saturating,
+}
+
+impl OverflowPolicy {
+    fn add<W: Word>(self, value_a: &W, value_b: &W) -> Option<W> {
+        match self {
+            OverflowPolicy::Error => value_a.checked_add(value_b),
+            OverflowPolicy::Wrapping => Some(value_a.wrapping_add(value_b)),
+            OverflowPolicy::Saturating => Some(value_a.saturating_add(value_b)),
+        }
+    }
+
+    fn mul<W: Word>(self, value_a: &W, value_b: &W) -> Option<W> {
+        match self {
+            OverflowPolicy::Error => value_a.checked_mul(value_b),
+            OverflowPolicy::Wrapping => Some(value_a.wrapping_mul(value_b)),
+            OverflowPolicy::Saturating => Some(value_a.saturating_mul(value_b)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MachineError {
+    Exiting,
+    InvalidInstruction(usize),
+    InvalidParameterMode(usize),
+    OutOfBound(usize),
+    NegativeAddress(i64),
+    /// The result of the instruction at `ip` doesn't fit in a word.
+    Overflow {
+        ip: usize,
+    },
+    InvalidInput(String),
+    OutputClosed,
+    Trace(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MachineReturn<W = IntMachineType> {
+    Output(W),
+    Exit(Vec<W>),
+    /// The input ran dry, `ip` still points at the Input instruction.
+    NeedsInput,
+    /// The step budget or the timeout ran out, running the machine again resumes it.
+    BudgetExhausted,
+}
+
+/// Outcome of executing a single instruction.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StepResult<W = IntMachineType> {
+    Continue,
+    Output(W),
+    /// The input ran dry, `ip` still points at the Input instruction.
+    NeedsInput,
+    Exit,
+}
+
+impl IntMachine {
+    pub fn new(ram: Vec<IntMachineType>) -> Self {
+        IntMachine::with_memory(Memory::new(ram))
+    }
+}
+
+impl<W: Word> IntMachine<W> {
+    pub fn with_memory(ram: Memory<W>) -> Self {
+        IntMachine {
+            ip: 0,
+            relative_base: W::zero(),
+            ram,
+            output_values: Vec::new(),
+            steps: 0,
+            overflow_policy: OverflowPolicy::default(),
+            step_budget: None,
+            timeout: None,
+            instruction_cache: Vec::new(),
+            instruction_cache_enabled: true,
+            tracer: None,
+            trace_event: None,
+        }
+    }
+
+    pub fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Limit the number of instructions executed by each call to `run` or `run_with`.
+    pub fn set_step_budget(&mut self, step_budget: Option<u64>) {
+        self.step_budget = step_budget;
+    }
+
+    pub fn step_budget(&self) -> Option<u64> {
+        self.step_budget
+    }
+
+    /// Limit the wall-clock time spent in each call to `run` or `run_with`.
+    ///
+    /// The clock is only checked every few instructions, so the limit can be
+    /// slightly exceeded, and a blocking `Input` isn't interrupted.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Enable or disable the decoded instruction cache, enabled by default.
+    ///
+    /// Without the cache, every instruction is decoded again through
+    /// `InstructionType::read_instruction` each time it's executed.
+    pub fn set_instruction_cache(&mut self, enabled: bool) {
+        self.instruction_cache_enabled = enabled;
+        self.instruction_cache = Vec::new();
+    }
+
+    /// Attach a tracer receiving an event for every executed instruction.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer<W> + Send>>) {
+        self.tracer = tracer;
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer<W> + Send>> {
+        self.tracer.take()
+    }
+
+    /// Number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> W {
+        self.relative_base.clone()
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.ram
+    }
+
+    pub fn output_values(&self) -> &[W] {
+        &self.output_values
+    }
+
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            ip: self.ip,
+            relative_base: self.relative_base.clone(),
+            steps: self.steps,
+            memory: self.ram.clone(),
+            output_values: self.output_values.clone(),
+        }
+    }
+
+    /// Bring the machine back to the state of `snapshot`, keeping its tracer and overflow policy.
+    pub fn restore(&mut self, snapshot: &Snapshot<W>) {
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base.clone();
+        self.steps = snapshot.steps;
+        self.ram = snapshot.memory.clone();
+        self.output_values = snapshot.output_values.clone();
+        self.instruction_cache.clear();
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot<W>) -> Self {
+        let mut machine = IntMachine::with_memory(snapshot.memory.clone());
+
+        machine.restore(snapshot);
+        machine
+    }
+
+    /// Copy the machine in O(1), memory pages being shared until one of the copies writes to them.
+    ///
+    /// The tracer isn't carried over to the new machine.
+    pub fn fork(&self) -> Self {
+        IntMachine {
+            ip: self.ip,
+            relative_base: self.relative_base.clone(),
+            ram: self.ram.clone(),
+            output_values: self.output_values.clone(),
+            steps: self.steps,
+            overflow_policy: self.overflow_policy,
+            step_budget: self.step_budget,
+            timeout: self.timeout,
+            instruction_cache: Vec::new(),
+            instruction_cache_enabled: self.instruction_cache_enabled,
+            tracer: None,
+            trace_event: None,
+        }
+    }
+
+    /// Write a value in memory, typically to patch the program before running it.
+    pub fn write_memory(&mut self, address: usize, value: W) -> Result<(), MachineError> {
+        self.write_at_position(address, value)
+    }
+
+    /// Opcode of the instruction at `ip`, a negative one being invalid.
+    pub(crate) fn read_opcode(&self) -> Result<usize, MachineError> {
+        self.read_at_position(self.ip)?
+            .to_usize()
+            .ok_or(MachineError::InvalidInstruction(usize::MAX))
+    }
+
+    fn decode_instruction(&mut self) -> Result<DecodedInstruction<W>, MachineError> {
+        if !self.instruction_cache_enabled {
+            let instruction = InstructionType::from_opcode(self.read_opcode()?)?;
+
+            return Ok(instruction.read_instruction(self)?.into());
+        }
+
+        if let Some(Some(decoded)) = self.instruction_cache.get(self.ip) {
+            return Ok(decoded.clone());
+        }
+
+        let decoded = DecodedInstruction::decode(self)?;
+
+        if self.ip < INSTRUCTION_CACHE_SIZE {
+            if self.instruction_cache.len() <= self.ip {
+                self.instruction_cache.resize(self.ip + 1, None);
+            }
+
+            self.instruction_cache[self.ip] = Some(decoded.clone());
+        }
+
+        Ok(decoded)
+    }
+
+    fn read_instruction(&mut self) -> Result<DecodedInstruction<W>, MachineError> {
+        let result = self.decode_instruction()?;
+
+        if result.instruction == InstructionType::Exit {
+            return Err(MachineError::Exiting);
+        }
+
+        self.ip += result.instruction.code_size();
+
+        Ok(result)
+    }
+
+    pub(crate) fn read_at_position(&self, position: usize) -> Result<W, MachineError> {
+        self.ram.read(position)
+    }
+
+    pub(crate) fn write_at_position(
+        &mut self,
+        position: usize,
+        value: W,
+    ) -> Result<(), MachineError> {
+        self.ram.write(position, value)?;
+
+        // Drop every cached instruction that could span the written address.
+        if !self.instruction_cache.is_empty() {
+            for address in position.saturating_sub(MAX_ARGUMENTS)..=position {
+                if let Some(entry) = self.instruction_cache.get_mut(address) {
+                    *entry = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, argument: &InstructionArgument<W>) -> Result<W, MachineError> {
+        let value = argument.get_value(self)?;
+
+        if self.trace_event.is_some() {
+            let address = argument.address(self)?;
+
+            if let Some(event) = &mut self.trace_event {
+                event.values.push(value.clone());
+
+                if argument.parameter_mode != ParameterMode::Immediate {
+                    event.reads.push(MemoryRead {
+                        address,
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn store(&mut self, argument: &InstructionArgument<W>, value: W) -> Result<(), MachineError> {
+        if self.trace_event.is_some() {
+            let address = argument.address(self)?;
+            let old_value = self.read_at_position(address)?;
+
+            if let Some(event) = &mut self.trace_event {
+                event.writes.push(MemoryWrite {
+                    address,
+                    old_value,
+                    new_value: value.clone(),
+                });
+            }
+        }
+
+        argument.write_value(self, value)
+    }
+
+    pub fn run(
+        &mut self,
+        ram_dump: bool,
+        break_at_output: bool,
+        input_values: Vec<W>,
+    ) -> Result<MachineReturn<W>, MachineError> {
+        let mut input = VecDeque::from(input_values);
+
+        self.run_with(ram_dump, break_at_output, &mut input, &mut ())
+    }
+
+    /// Execute a single instruction.
+    pub fn step_with<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<StepResult<W>, MachineError>
+    where
+        I: Input<W> + ?Sized,
+        O: Output<W> + ?Sized,
+    {
+        if self.tracer.is_none() {
+            let result = self.execute(input, output);
+
+            if let Ok(StepResult::Continue) | Ok(StepResult::Output(_)) = result {
+                self.steps += 1;
+            }
+
+            return result;
+        }
+
+        let ip = self.ip;
+        let opcode = self.read_at_position(ip)?;
+
+        self.trace_event = Some(TraceEvent::new(
+            self.steps,
+            ip,
+            opcode,
+            InstructionType::Exit,
+            self.relative_base.clone(),
+        ));
+
+        let result = self.execute(input, output);
+        let event = self.trace_event.take();
+
+        match (&result, event) {
+            (Ok(StepResult::NeedsInput), _) | (Err(_), _) | (_, None) => {}
+            (Ok(step_result), Some(event)) => {
+                if *step_result != StepResult::Exit {
+                    self.steps += 1;
+                }
+
+                if let Some(tracer) = &mut self.tracer {
+                    tracer
+                        .trace(&event)
+                        .map_err(|error| MachineError::Trace(error.to_string()))?;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn execute<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<StepResult<W>, MachineError>
+    where
+        I: Input<W> + ?Sized,
+        O: Output<W> + ?Sized,
+    {
+        let ip = self.ip;
+        let decoded = match self.read_instruction() {
+            Err(MachineError::Exiting) => return Ok(StepResult::Exit),
+            decoded => decoded?,
+        };
+        let arguments = decoded.arguments();
+
+        if let Some(event) = &mut self.trace_event {
+            event.instruction = decoded.instruction;
+            event.operands = arguments
+                .iter()
+                .map(|argument| Operand::new(argument.parameter_mode, argument.value.clone()))
+                .collect();
+        }
+
+        match decoded.instruction {
+            InstructionType::Addition => {
+                let value_a = self.load(&arguments[0])?;
+                let value_b = self.load(&arguments[1])?;
+
+                let result_value = self
+                    .overflow_policy
+                    .add(&value_a, &value_b)
+                    .ok_or(MachineError::Overflow { ip })?;
+
+                self.store(&arguments[2], result_value)?;
+            }
+            InstructionType::Multiplication => {
+                let value_a = self.load(&arguments[0])?;
+                let value_b = self.load(&arguments[1])?;
+
+                let result_value = self
+                    .overflow_policy
+                    .mul(&value_a, &value_b)
+                    .ok_or(MachineError::Overflow { ip })?;
+
+                self.store(&arguments[2], result_value)?;
+            }
+            InstructionType::Input => {
+                let input_value = match input.read()? {
+                    Some(input_value) => input_value,
+                    None => {
+                        self.ip -= decoded.instruction.code_size();
+
+                        return Ok(StepResult::NeedsInput);
+                    }
+                };
+
+                self.store(&arguments[0], input_value)?;
+            }
+            InstructionType::Output => {
+                let value = self.load(&arguments[0])?;
+
+                self.output_values.push(value.clone());
+                output.write(value.clone())?;
+
+                return Ok(StepResult::Output(value));
+            }
+            InstructionType::JumpIfTrue => {
+                let value = self.load(&arguments[0])?;
+                let new_ip = self.load(&arguments[1])?;
+
+                if value != W::zero() {
+                    self.ip = to_address(&new_ip)?;
+                }
+            }
+            InstructionType::JumpIfFalse => {
+                let value = self.load(&arguments[0])?;
+                let new_ip = self.load(&arguments[1])?;
+
+                if value == W::zero() {
+                    self.ip = to_address(&new_ip)?;
+                }
+            }
+            InstructionType::LessThan => {
+                let value_a = self.load(&arguments[0])?;
+                let value_b = self.load(&arguments[1])?;
+
+                let result_value = value_a.less_than(&value_b);
+
+                self.store(&arguments[2], result_value)?;
+            }
+            InstructionType::Equals => {
+                let value_a = self.load(&arguments[0])?;
+                let value_b = self.load(&arguments[1])?;
+
+                let result_value = value_a.equals(&value_b);
+
+                self.store(&arguments[2], result_value)?;
+            }
+            InstructionType::AdjustRelativeBase => {
+                let value = self.load(&arguments[0])?;
+
+                self.relative_base = self
+                    .overflow_policy
+                    .add(&self.relative_base, &value)
+                    .ok_or(MachineError::Overflow { ip })?;
+            }
+            InstructionType::Exit => return Ok(StepResult::Exit),
+        }
+
+        Ok(StepResult::Continue)
+    }
+
+    /// Run the machine reading from `input` and writing to `output`.
+    ///
+    /// Output values are also kept in the machine and returned on exit.
+    pub fn run_with<I, O>(
+        &mut self,
+        ram_dump: bool,
+        break_at_output: bool,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<MachineReturn<W>, MachineError>
+    where
+        I: Input<W> + ?Sized,
+        O: Output<W> + ?Sized,
+    {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut steps = 0;
+
+        loop {
+            if Some(steps) == self.step_budget {
+                return Ok(MachineReturn::BudgetExhausted);
+            }
+
+            if let Some(deadline) = deadline {
+                if steps % TIMEOUT_CHECK_INTERVAL == 0 && deadline <= Instant::now() {
+                    return Ok(MachineReturn::BudgetExhausted);
+                }
+            }
+
+            steps += 1;
+
+            match self.step_with(input, output)? {
+                StepResult::Continue => {}
+                StepResult::Output(value) => {
+                    if break_at_output {
+                        return Ok(MachineReturn::Output(value));
+                    }
+                }
+                StepResult::NeedsInput => return Ok(MachineReturn::NeedsInput),
+                StepResult::Exit => break,
+            }
+        }
+
+        if ram_dump {
+            Ok(MachineReturn::Exit(self.ram.to_vec()?))
+        } else {
+            Ok(MachineReturn::Exit(self.output_values.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{IntMachine, MachineError, MachineReturn, OverflowPolicy};
+    use crate::memory::{Memory, DEFAULT_MEMORY_LIMIT};
+    use crate::word::{convert_code, Word};
+    use crate::IntMachineType;
+
+    fn run_machine(
+        code: Vec<IntMachineType>,
+        expected_result: Vec<IntMachineType>,
+        input_value: Vec<IntMachineType>,
+        ram_dump: bool,
+    ) {
+        let mut machine = IntMachine::new(code);
+
+        assert_eq!(
+            machine.run(ram_dump, false, input_value),
+            Ok(MachineReturn::Exit(expected_result))
+        );
+    }
+
+    #[test]
+    pub fn test_instructions() {
+        run_machine(vec![1, 0, 0, 0, 99], vec![2, 0, 0, 0, 99], vec![], true);
+        run_machine(vec![2, 3, 0, 3, 99], vec![2, 3, 0, 6, 99], vec![], true);
+        run_machine(
+            vec![2, 4, 4, 5, 99, 0],
+            vec![2, 4, 4, 5, 99, 9801],
+            vec![],
+            true,
+        );
+        run_machine(
+            vec![1, 1, 1, 4, 99, 5, 6, 0, 99],
+            vec![30, 1, 1, 4, 2, 5, 6, 0, 99],
+            vec![],
+            true,
+        );
+        run_machine(
+            vec![1002, 4, 3, 4, 33],
+            vec![1002, 4, 3, 4, 99],
+            vec![],
+            true,
+        );
+        run_machine(
+            vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            vec![1],
+            vec![8],
+            false,
+        );
+        run_machine(
+            vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8],
+            vec![0],
+            vec![8],
+            false,
+        );
+        run_machine(
+            vec![3, 3, 1108, -1, 8, 3, 4, 3, 99],
+            vec![1],
+            vec![8],
+            false,
+        );
+        run_machine(
+            vec![3, 3, 1107, -1, 8, 3, 4, 3, 99],
+            vec![1],
+            vec![-8],
+            false,
+        );
+        run_machine(
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            vec![0],
+            vec![0],
+            false,
+        );
+        run_machine(
+            vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+            vec![1],
+            vec![42],
+            false,
+        );
+    }
+
+    #[test]
+    pub fn test_relative_mode() {
+        run_machine(vec![109, 5, 204, 1, 99, 0, 42], vec![42], vec![], false);
+        run_machine(
+            vec![109, 7, 203, 1, 204, 1, 99, 0, 0],
+            vec![1234],
+            vec![1234],
+            false,
+        );
+        run_machine(
+            vec![109, 7, 203, 1, 204, 1, 99, 0, 0],
+            vec![109, 7, 203, 1, 204, 1, 99, 0, -5],
+            vec![-5],
+            true,
+        );
+        run_machine(
+            vec![109, 10, 109, -4, 204, 1, 99, 42],
+            vec![42],
+            vec![],
+            false,
+        );
+    }
+
+    #[test]
+    pub fn test_memory_growth() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        run_machine(quine.clone(), quine.clone(), vec![], false);
+
+        let mut machine = IntMachine::with_memory(Memory::paged(quine.clone()));
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::Exit(quine))
+        );
+
+        run_machine(
+            vec![1101, 2, 3, 7, 99],
+            vec![1101, 2, 3, 7, 99, 0, 0, 5],
+            vec![],
+            true,
+        );
+        run_machine(vec![4, 1000, 99], vec![0], vec![], false);
+
+        // Dumping every word up to a far away write would take terabytes.
+        let code = vec![1101, 2, 3, 1i64 << 40, 99];
+        let mut machine = IntMachine::with_memory(Memory::paged(code.clone()));
+        assert_eq!(
+            machine.run(true, false, vec![]),
+            Err(MachineError::OutOfBound(DEFAULT_MEMORY_LIMIT))
+        );
+
+        let mut machine = IntMachine::with_memory(Memory::paged(code));
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::Exit(vec![]))
+        );
+        assert_eq!(machine.memory().read(1 << 40), Ok(5));
+    }
+
+    #[test]
+    pub fn test_memory_limit() {
+        let mut machine = IntMachine::with_memory(
+            Memory::<i64>::new(vec![1101, 2, 3, 7, 99]).with_limit(Some(6)),
+        );
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Err(MachineError::OutOfBound(7))
+        );
+
+        let mut machine = IntMachine::new(vec![4, -1, 99]);
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Err(MachineError::NegativeAddress(-1))
+        );
+    }
+
+    #[test]
+    pub fn test_negative_jump() {
+        for code in [vec![1105, 1, -1], vec![1106, 0, -7]] {
+            let target = code[2];
+            let mut machine = IntMachine::new(code);
+
+            assert_eq!(
+                machine.run(false, false, vec![]),
+                Err(MachineError::NegativeAddress(target))
+            );
+        }
+
+        run_machine(vec![1105, 0, -1, 99], vec![], vec![], false);
+        run_machine(vec![1106, 1, -1, 99], vec![], vec![], false);
+    }
+
+    #[test]
+    pub fn test_large_numbers() {
+        run_machine(
+            vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+            vec![1219070632396864],
+            vec![],
+            false,
+        );
+        run_machine(
+            vec![104, 1125899906842624, 99],
+            vec![1125899906842624],
+            vec![],
+            false,
+        );
+    }
+
+    fn run_as<W: Word>(
+        code: &[IntMachineType],
+        input: &[IntMachineType],
+    ) -> Result<MachineReturn<W>, MachineError> {
+        let mut machine = IntMachine::with_memory(Memory::new(convert_code(code).unwrap()));
+
+        machine.run(false, false, convert_code(input).unwrap())
+    }
+
+    #[test]
+    pub fn test_instruction_cache() {
+        // Increments the immediate operand of its own Output instruction.
+        let code = vec![104, 1, 1001, 1, 1, 1, 1007, 1, 4, 20, 1005, 20, 0, 99];
+
+        for enabled in &[true, false] {
+            let mut machine = IntMachine::new(code.clone());
+            machine.set_instruction_cache(*enabled);
+
+            assert_eq!(
+                machine.run(false, false, vec![]),
+                Ok(MachineReturn::Exit(vec![1, 2, 3]))
+            );
+
+            let mut machine = IntMachine::new(vec![3, 0, 3, 0, 4, 0, 99]);
+            machine.set_instruction_cache(*enabled);
+
+            assert_eq!(
+                machine.run(false, false, vec![1101, 5]),
+                Ok(MachineReturn::Exit(vec![5]))
+            );
+        }
+
+        let mut machine = IntMachine::new(vec![1101, 1, 1, 5, 104, 0, 99]);
+        let snapshot = machine.snapshot();
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::Exit(vec![2]))
+        );
+
+        machine.restore(&snapshot);
+        machine.write_memory(1, 4).unwrap();
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::Exit(vec![5]))
+        );
+    }
+
+    #[test]
+    pub fn test_fork() {
+        let mut code = vec![3, 2000, 4, 2000, 99];
+        code.resize(3000, 0);
+
+        let mut machine = IntMachine::new(code);
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::NeedsInput)
+        );
+
+        let mut forks = (1..=3).map(|_| machine.fork()).collect::<Vec<IntMachine>>();
+        assert_eq!(machine.memory().shared_pages(), 3);
+
+        for (value, fork) in forks.iter_mut().enumerate() {
+            assert_eq!(
+                fork.run(false, false, vec![value as IntMachineType]),
+                Ok(MachineReturn::Exit(vec![value as IntMachineType]))
+            );
+            assert_eq!(fork.memory().shared_pages(), 2);
+        }
+
+        assert_eq!(machine.memory().read(2000), Ok(0));
+        assert_eq!(
+            machine.run(false, false, vec![7]),
+            Ok(MachineReturn::Exit(vec![7]))
+        );
+    }
+
+    #[test]
+    pub fn test_word_types() {
+        let quine = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        assert_eq!(
+            run_as::<i32>(&quine, &[]),
+            Ok(MachineReturn::Exit(convert_code(&quine).unwrap()))
+        );
+        assert_eq!(
+            run_as::<i128>(&quine, &[]),
+            Ok(MachineReturn::Exit(convert_code(&quine).unwrap()))
+        );
+
+        let code = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+
+        for input in &[0, 5] {
+            let expected = match run_as::<i64>(&code, &[*input]) {
+                Ok(MachineReturn::Exit(outputs)) => outputs,
+                result => panic!("unexpected result {:?}", result),
+            };
+
+            assert_eq!(
+                run_as::<i32>(&code, &[*input]),
+                Ok(MachineReturn::Exit(convert_code(&expected).unwrap()))
+            );
+            assert_eq!(
+                run_as::<i128>(&code, &[*input]),
+                Ok(MachineReturn::Exit(convert_code(&expected).unwrap()))
+            );
+        }
+
+        let code = [1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+
+        assert_eq!(
+            run_as::<i32>(&code, &[]),
+            Err(MachineError::Overflow { ip: 0 })
+        );
+        assert_eq!(
+            run_as::<i128>(&code, &[]),
+            Ok(MachineReturn::Exit(vec![1219070632396864]))
+        );
+
+        let max = IntMachineType::MAX;
+        let code = [1102, max, max, 7, 4, 7, 99, 0];
+
+        assert_eq!(
+            run_as::<i64>(&code, &[]),
+            Err(MachineError::Overflow { ip: 0 })
+        );
+        assert_eq!(
+            run_as::<i128>(&code, &[]),
+            Ok(MachineReturn::Exit(vec![max as i128 * max as i128]))
+        );
+    }
+
+    #[test]
+    pub fn test_overflow() {
+        let max = IntMachineType::MAX;
+        let code = vec![1101, max, 1, 9, 1102, max, 2, 10, 99, 0, 0];
+
+        let mut machine = IntMachine::new(code.clone());
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Err(MachineError::Overflow { ip: 0 })
+        );
+
+        let mut machine = IntMachine::new(code.clone());
+        machine.set_overflow_policy(OverflowPolicy::Wrapping);
+
+        match machine.run(true, false, vec![]) {
+            Ok(MachineReturn::Exit(ram)) => assert_eq!(ram[9..], [IntMachineType::MIN, -2]),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let mut machine = IntMachine::new(code);
+        machine.set_overflow_policy(OverflowPolicy::Saturating);
+
+        match machine.run(true, false, vec![]) {
+            Ok(MachineReturn::Exit(ram)) => assert_eq!(ram[9..], [max, max]),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let mut machine = IntMachine::new(vec![109, max, 109, 1, 99]);
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Err(MachineError::Overflow { ip: 2 })
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    pub fn test_bigint() {
+        use num_bigint::BigInt;
+
+        let max = BigInt::from(IntMachineType::MAX);
+        let code = vec![1102, 1, 1, 7, 4, 7, 99, 0];
+        let mut code = code.into_iter().map(BigInt::from).collect::<Vec<BigInt>>();
+
+        code[1] = max.clone();
+        code[2] = max.clone();
+
+        let mut machine = IntMachine::with_memory(Memory::new(code));
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::Exit(vec![&max * &max]))
+        );
+    }
+
+    #[test]
+    pub fn test_invalid_parameter_mode() {
+        let mut machine = IntMachine::new(vec![301, 0, 0, 0, 99]);
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Err(MachineError::InvalidParameterMode(301))
+        );
+    }
+
+    #[test]
+    pub fn test_needs_input() {
+        let mut machine = IntMachine::new(vec![3, 0, 3, 0, 4, 0, 99]);
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::NeedsInput)
+        );
+        assert_eq!(machine.ip(), 0);
+        assert_eq!(
+            machine.run(false, false, vec![1]),
+            Ok(MachineReturn::NeedsInput)
+        );
+        assert_eq!(machine.ip(), 2);
+        assert_eq!(
+            machine.run(false, false, vec![5]),
+            Ok(MachineReturn::Exit(vec![5]))
+        );
+
+        let mut input = VecDeque::new();
+        let mut machine = IntMachine::new(vec![3, 12, 1001, 12, 1, 12, 4, 12, 1105, 1, 0, 99]);
+
+        for value in 0..3 {
+            assert_eq!(
+                machine.run_with(false, true, &mut input, &mut ()),
+                Ok(MachineReturn::NeedsInput)
+            );
+
+            input.push_back(value);
+
+            assert_eq!(
+                machine.run_with(false, true, &mut input, &mut ()),
+                Ok(MachineReturn::Output(value + 1))
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_run_with() {
+        let echo = vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+
+        let mut input = VecDeque::from(vec![41]);
+        let mut output = VecDeque::new();
+        let mut machine = IntMachine::new(echo.clone());
+
+        assert_eq!(
+            machine.run_with(false, false, &mut input, &mut output),
+            Ok(MachineReturn::Exit(vec![42]))
+        );
+        assert_eq!(output, VecDeque::from(vec![42]));
+
+        let mut values = vec![1, 2, 3].into_iter();
+        let mut outputs = Vec::new();
+        let mut machine = IntMachine::new(vec![3, 0, 3, 0, 4, 0, 99]);
+
+        machine
+            .run_with(false, false, &mut || values.next(), &mut |value| {
+                outputs.push(value)
+            })
+            .unwrap();
+        assert_eq!(outputs, vec![2]);
+        assert_eq!(values.next(), Some(3));
+
+        let (input_sender, mut input_receiver) = channel();
+        let (mut output_sender, output_receiver) = channel();
+
+        let handle = thread::spawn(move || {
+            let mut machine = IntMachine::new(echo);
+
+            machine.run_with(false, false, &mut input_receiver, &mut output_sender)
+        });
+
+        input_sender.send(99).unwrap();
+
+        assert_eq!(output_receiver.recv(), Ok(100));
+        assert_eq!(handle.join().unwrap(), Ok(MachineReturn::Exit(vec![100])));
+    }
+
+    #[test]
+    pub fn test_budget() {
+        let mut machine = IntMachine::new(vec![1105, 1, 0]);
+        machine.set_step_budget(Some(10));
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::BudgetExhausted)
+        );
+        assert_eq!(machine.steps(), 10);
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::BudgetExhausted)
+        );
+        assert_eq!(machine.steps(), 20);
+
+        let mut machine = IntMachine::new(vec![1105, 1, 0]);
+        machine.set_timeout(Some(Duration::from_millis(10)));
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::BudgetExhausted)
+        );
+
+        let code = vec![3, 12, 1001, 12, 1, 12, 4, 12, 1105, 1, 0, 99];
+        let mut input = VecDeque::from(vec![1, 2, 3]);
+        let mut outputs = Vec::new();
+        let mut machine = IntMachine::new(code);
+        machine.set_step_budget(Some(3));
+
+        while let Ok(MachineReturn::BudgetExhausted) =
+            machine.run_with(false, false, &mut input, &mut outputs)
+        {}
+
+        assert_eq!(outputs, vec![2, 3, 4]);
+        assert_eq!(machine.ip(), 0);
+    }
+
+    #[test]
+    pub fn test_break_at_output() {
+        let mut machine = IntMachine::new(vec![104, 1, 104, 2, 99]);
+
+        assert_eq!(
+            machine.run(false, true, vec![]),
+            Ok(MachineReturn::Output(1))
+        );
+        assert_eq!(
+            machine.run(false, true, vec![]),
+            Ok(MachineReturn::Output(2))
+        );
+        assert_eq!(
+            machine.run(false, true, vec![]),
+            Ok(MachineReturn::Exit(vec![1, 2]))
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::machine::MachineError;
+use crate::word::Word;
+use crate::IntMachineType;
+
+/// Number of words held by a single page of a memory.
+pub const PAGE_SIZE: usize = 1024;
+
+/// Default upper address limit of a linear memory (128MiB of `i64` words).
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+/// Pages are shared between clones of a memory and copied on their first write.
+type Page<W> = Arc<[W]>;
+
+#[derive(Debug, Clone)]
+enum Storage<W> {
+    /// Every page from address 0 up to the last written one.
+    Linear(Arc<Vec<Page<W>>>),
+    /// Only the pages being written to.
+    Paged(Arc<HashMap<usize, Page<W>>>),
+}
+
+/// Memory of an `IntMachine`.
+///
+/// Addresses past the program image read as zero and are allocated on write.
+/// Any access at or after the limit fails with `MachineError::OutOfBound`.
+///
+/// Cloning a memory is O(1), its pages being copied on write.
+#[derive(Debug, Clone)]
+pub struct Memory<W = IntMachineType> {
+    storage: Storage<W>,
+    len: usize,
+    limit: Option<usize>,
+}
+
+fn zero_page<W: Word>() -> Page<W> {
+    vec![W::zero(); PAGE_SIZE].into()
+}
+
+fn to_page<W: Word>(values: &[W]) -> Page<W> {
+    let mut page = values.to_vec();
+
+    page.resize(PAGE_SIZE, W::zero());
+    page.into()
+}
+
+impl<W: Word> Memory<W> {
+    /// Create a memory with a dense page table that grows on demand.
+    pub fn new(ram: Vec<W>) -> Self {
+        Memory {
+            len: ram.len(),
+            storage: Storage::Linear(Arc::new(ram.chunks(PAGE_SIZE).map(to_page).collect())),
+            limit: Some(DEFAULT_MEMORY_LIMIT),
+        }
+    }
+
+    /// Create a sparse memory that only allocates the pages being written to.
+    pub fn paged(ram: Vec<W>) -> Self {
+        let pages = ram.chunks(PAGE_SIZE).map(to_page).enumerate().collect();
+
+        Memory {
+            len: ram.len(),
+            storage: Storage::Paged(Arc::new(pages)),
+            limit: None,
+        }
+    }
+
+    /// Set the first address that isn't accessible anymore, `None` meaning no limit.
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Size of the program image plus everything written after it.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_paged(&self) -> bool {
+        matches!(self.storage, Storage::Paged(_))
+    }
+
+    /// Allocated pages sorted by index, `None` for a linear memory.
+    pub(crate) fn pages(&self) -> Option<Vec<(usize, &[W])>> {
+        match &self.storage {
+            Storage::Linear(_) => None,
+            Storage::Paged(pages) => {
+                let mut result = pages
+                    .iter()
+                    .map(|(index, page)| (*index, &page[..]))
+                    .collect::<Vec<(usize, &[W])>>();
+
+                result.sort_by_key(|(index, _)| *index);
+                Some(result)
+            }
+        }
+    }
+
+    /// Rebuild a paged memory, pages shorter than `PAGE_SIZE` being padded with zeros.
+    pub(crate) fn from_pages(pages: Vec<(usize, Vec<W>)>, len: usize) -> Self {
+        let pages = pages
+            .into_iter()
+            .map(|(index, page)| (index, to_page(&page)))
+            .collect();
+
+        Memory {
+            len,
+            storage: Storage::Paged(Arc::new(pages)),
+            limit: None,
+        }
+    }
+
+    /// Number of pages shared with another memory, which a write would copy.
+    pub fn shared_pages(&self) -> usize {
+        fn count<'a, W: 'a>(table_shared: bool, pages: impl Iterator<Item = &'a Page<W>>) -> usize {
+            pages
+                .filter(|page| table_shared || Arc::strong_count(page) > 1)
+                .count()
+        }
+
+        match &self.storage {
+            Storage::Linear(pages) => count(Arc::strong_count(pages) > 1, pages.iter()),
+            Storage::Paged(pages) => count(Arc::strong_count(pages) > 1, pages.values()),
+        }
+    }
+
+    fn check_bound(&self, position: usize) -> Result<(), MachineError> {
+        match self.limit {
+            Some(limit) if limit <= position => Err(MachineError::OutOfBound(position)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn read(&self, position: usize) -> Result<W, MachineError> {
+        self.check_bound(position)?;
+
+        let page = match &self.storage {
+            Storage::Linear(pages) => pages.get(position / PAGE_SIZE),
+            Storage::Paged(pages) => pages.get(&(position / PAGE_SIZE)),
+        };
+
+        Ok(page.map_or_else(W::zero, |page| page[position % PAGE_SIZE].clone()))
+    }
+
+    pub fn write(&mut self, position: usize, value: W) -> Result<(), MachineError> {
+        self.check_bound(position)?;
+
+        let index = position / PAGE_SIZE;
+
+        let page = match &mut self.storage {
+            Storage::Linear(pages) => {
+                let pages = Arc::make_mut(pages);
+
+                if pages.len() <= index {
+                    pages.resize(index + 1, zero_page());
+                }
+
+                &mut pages[index]
+            }
+            Storage::Paged(pages) => Arc::make_mut(pages).entry(index).or_insert_with(zero_page),
+        };
+
+        Arc::make_mut(page)[position % PAGE_SIZE] = value;
+
+        self.len = self.len.max(position + 1);
+        Ok(())
+    }
+
+    /// Dump the memory from address 0 up to `len()`.
+    ///
+    /// A paged memory longer than `DEFAULT_MEMORY_LIMIT` fails with `MachineError::OutOfBound`
+    /// instead of allocating every word up to its last written address.
+    pub fn to_vec(&self) -> Result<Vec<W>, MachineError> {
+        match &self.storage {
+            Storage::Linear(pages) => {
+                let mut result = pages
+                    .iter()
+                    .flat_map(|page| page.iter().cloned())
+                    .collect::<Vec<W>>();
+
+                result.truncate(self.len);
+                Ok(result)
+            }
+            Storage::Paged(_) if self.len > DEFAULT_MEMORY_LIMIT => {
+                Err(MachineError::OutOfBound(DEFAULT_MEMORY_LIMIT))
+            }
+            Storage::Paged(_) => (0..self.len).map(|position| self.read(position)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Memory, DEFAULT_MEMORY_LIMIT, PAGE_SIZE};
+    use crate::MachineError;
+
+    #[test]
+    pub fn test_linear_growth() {
+        let mut memory = Memory::<i64>::new(vec![1, 2, 3]);
+
+        assert_eq!(memory.read(2), Ok(3));
+        assert_eq!(memory.read(10), Ok(0));
+        assert_eq!(memory.len(), 3);
+
+        memory.write(5, 42).unwrap();
+
+        assert_eq!(memory.len(), 6);
+        assert_eq!(memory.to_vec(), Ok(vec![1, 2, 3, 0, 0, 42]));
+    }
+
+    #[test]
+    pub fn test_paged() {
+        let mut memory = Memory::<i64>::paged(vec![1, 2, 3]);
+
+        assert_eq!(memory.read(1), Ok(2));
+        assert_eq!(memory.read(1 << 40), Ok(0));
+
+        memory.write(1 << 40, 7).unwrap();
+        memory.write(PAGE_SIZE + 1, 8).unwrap();
+
+        assert_eq!(memory.read(1 << 40), Ok(7));
+        assert_eq!(memory.read(PAGE_SIZE + 1), Ok(8));
+        assert_eq!(memory.len(), (1 << 40) + 1);
+        assert_eq!(
+            memory.to_vec(),
+            Err(MachineError::OutOfBound(DEFAULT_MEMORY_LIMIT))
+        );
+
+        let mut memory = Memory::<i64>::paged(vec![1, 2, 3]);
+        memory.write(4, 5).unwrap();
+
+        assert_eq!(memory.to_vec(), Ok(vec![1, 2, 3, 0, 5]));
+    }
+
+    #[test]
+    pub fn test_copy_on_write() {
+        let mut memory = Memory::<i64>::new(vec![1; 3 * PAGE_SIZE]);
+        let copy = memory.clone();
+
+        assert_eq!(memory.shared_pages(), 3);
+
+        memory.write(PAGE_SIZE, 2).unwrap();
+        memory.write(5 * PAGE_SIZE, 3).unwrap();
+
+        // Pages 3 and 4 were never written, they share the same zero page.
+        assert_eq!(memory.shared_pages(), 4);
+        assert_eq!(copy.shared_pages(), 2);
+        assert_eq!(copy.read(PAGE_SIZE), Ok(1));
+        assert_eq!(copy.read(5 * PAGE_SIZE), Ok(0));
+        assert_eq!(copy.len(), 3 * PAGE_SIZE);
+        assert_eq!(memory.read(PAGE_SIZE), Ok(2));
+        assert_eq!(memory.len(), 5 * PAGE_SIZE + 1);
+
+        let mut memory = Memory::<i64>::paged(vec![1, 2, 3]);
+        let copy = memory.clone();
+
+        memory.write(1, 5).unwrap();
+        memory.write(1 << 40, 6).unwrap();
+
+        assert_eq!(copy.to_vec(), Ok(vec![1, 2, 3]));
+        assert_eq!(copy.read(1 << 40), Ok(0));
+        assert_eq!(memory.read(1), Ok(5));
+        assert_eq!(memory.shared_pages(), 0);
+    }
+
+    #[test]
+    pub fn test_limit() {
+        let mut memory = Memory::<i64>::new(vec![1, 2, 3]).with_limit(Some(8));
+
+        assert_eq!(memory.read(7), Ok(0));
+        assert_eq!(memory.read(8), Err(MachineError::OutOfBound(8)));
+        assert_eq!(memory.write(8, 1), Err(MachineError::OutOfBound(8)));
+
+        let mut memory = Memory::<i64>::paged(vec![]).with_limit(Some(PAGE_SIZE));
+
+        assert_eq!(
+            memory.write(PAGE_SIZE, 1),
+            Err(MachineError::OutOfBound(PAGE_SIZE))
+        );
+    }
+}
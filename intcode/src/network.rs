@@ -0,0 +1,608 @@
+//! Networks of machines whose outputs are routed to the inputs of other machines.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::io::{Input, Output};
+use crate::machine::{IntMachine, MachineError, MachineReturn};
+use crate::word::Word;
+use crate::IntMachineType;
+
+/// Index of a node, returned by `Network::add_node`.
+pub type NodeId = usize;
+
+/// How a network stopped running.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NetworkState {
+    /// Every node halted.
+    Halted,
+    /// No node can make progress, the listed nodes are waiting for input.
+    Deadlock(Vec<NodeId>),
+}
+
+/// A node of the network failed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NetworkError {
+    pub node: NodeId,
+    pub error: MachineError,
+}
+
+struct Node<W> {
+    machine: IntMachine<W>,
+    input: VecDeque<W>,
+    targets: Vec<NodeId>,
+    halted: bool,
+}
+
+/// How long a blocked thread waits for a value before checking for a deadlock.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Bookkeeping shared by the threads of `Network::run_threaded`.
+struct Shared {
+    blocked: Vec<bool>,
+    /// Values sent to each node and not received yet.
+    pending: Vec<usize>,
+    halted: Vec<bool>,
+    stop: bool,
+}
+
+impl Shared {
+    fn deadlocked(&self) -> bool {
+        (0..self.halted.len())
+            .all(|node| self.halted[node] || (self.blocked[node] && self.pending[node] == 0))
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct ThreadInput<'a, W> {
+    node: NodeId,
+    receiver: Receiver<W>,
+    shared: &'a Mutex<Shared>,
+}
+
+impl<W> Input<W> for ThreadInput<'_, W> {
+    /// Block until a value is received, returning `None` once the network stops.
+    fn read(&mut self) -> Result<Option<W>, MachineError> {
+        if let Ok(value) = self.receiver.try_recv() {
+            lock(self.shared).pending[self.node] -= 1;
+            return Ok(Some(value));
+        }
+
+        lock(self.shared).blocked[self.node] = true;
+
+        loop {
+            match self.receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(value) => {
+                    let mut shared = lock(self.shared);
+
+                    shared.blocked[self.node] = false;
+                    shared.pending[self.node] -= 1;
+                    return Ok(Some(value));
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut shared = lock(self.shared);
+
+                    if shared.stop || shared.deadlocked() {
+                        shared.stop = true;
+                        return Ok(None);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            }
+        }
+    }
+}
+
+struct ThreadOutput<'a, W> {
+    targets: Vec<NodeId>,
+    senders: Vec<Sender<W>>,
+    shared: &'a Mutex<Shared>,
+}
+
+impl<W: Clone> Output<W> for ThreadOutput<'_, W> {
+    /// Values sent to a halted node are kept in its channel, like `Network::run` queues them.
+    fn write(&mut self, value: W) -> Result<(), MachineError> {
+        let mut shared = lock(self.shared);
+
+        for target in &self.targets {
+            if self.senders[*target].send(value.clone()).is_ok() {
+                shared.pending[*target] += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Machines connected by routes, every output of a node being sent to each of its targets.
+///
+/// Nodes are run in turn until they halt or wait for input, which allows
+/// chains, rings, fan-out and fan-in topologies.
+pub struct Network<W = IntMachineType> {
+    nodes: Vec<Node<W>>,
+}
+
+impl<W: Word> Default for Network<W> {
+    fn default() -> Self {
+        Network::new()
+    }
+}
+
+impl<W: Word> Network<W> {
+    pub fn new() -> Self {
+        Network { nodes: Vec::new() }
+    }
+
+    pub fn add_node(&mut self, machine: IntMachine<W>) -> NodeId {
+        self.nodes.push(Node {
+            machine,
+            input: VecDeque::new(),
+            targets: Vec::new(),
+            halted: false,
+        });
+
+        self.nodes.len() - 1
+    }
+
+    /// Route every output of `from` to the input of `to`.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        assert!(to < self.nodes.len(), "unknown node {}", to);
+
+        self.nodes[from].targets.push(to);
+    }
+
+    /// Queue a value on the input of `node`, typically before running the network.
+    pub fn push_input(&mut self, node: NodeId, value: W) {
+        self.nodes[node].input.push_back(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn machine(&self, node: NodeId) -> &IntMachine<W> {
+        &self.nodes[node].machine
+    }
+
+    pub fn machine_mut(&mut self, node: NodeId) -> &mut IntMachine<W> {
+        &mut self.nodes[node].machine
+    }
+
+    /// Every value output by `node` so far.
+    pub fn outputs(&self, node: NodeId) -> &[W] {
+        self.nodes[node].machine.output_values()
+    }
+
+    /// Run one node until it halts or waits for input, returning whether it executed anything.
+    ///
+    /// The outputs of the node are routed even when it fails.
+    fn run_node(&mut self, node: NodeId) -> Result<bool, NetworkError> {
+        let mut outputs = Vec::new();
+
+        let current = &mut self.nodes[node];
+        let steps = current.machine.steps();
+
+        let result = current
+            .machine
+            .run_with(false, false, &mut current.input, &mut outputs);
+
+        if let Ok(MachineReturn::Exit(_)) = result {
+            current.halted = true;
+        }
+
+        let progress = current.machine.steps() != steps || current.halted;
+        let targets = current.targets.clone();
+
+        for value in outputs {
+            for target in &targets {
+                self.nodes[*target].input.push_back(value.clone());
+            }
+        }
+
+        result.map_err(|error| NetworkError { node, error })?;
+
+        Ok(progress)
+    }
+
+    /// Run the nodes in turn until they all halt or none of them can make progress.
+    ///
+    /// Like with `run_threaded`, a failed node is only reported once the other
+    /// nodes ran as far as they could, the lowest failed node winning.
+    pub fn run(&mut self) -> Result<NetworkState, NetworkError> {
+        let mut errors: Vec<NetworkError> = Vec::new();
+
+        loop {
+            let mut progress = false;
+
+            for node in 0..self.nodes.len() {
+                if self.nodes[node].halted || errors.iter().any(|error| error.node == node) {
+                    continue;
+                }
+
+                match self.run_node(node) {
+                    Ok(node_progress) => progress |= node_progress,
+                    Err(error) => {
+                        errors.push(error);
+                        progress = true;
+                    }
+                }
+            }
+
+            if !progress && !errors.is_empty() {
+                errors.sort_by_key(|error| error.node);
+
+                return Err(errors.remove(0));
+            }
+
+            let waiting = (0..self.nodes.len())
+                .filter(|node| !self.nodes[*node].halted)
+                .collect::<Vec<NodeId>>();
+
+            if waiting.is_empty() {
+                return Ok(NetworkState::Halted);
+            }
+
+            if !progress {
+                return Ok(NetworkState::Deadlock(waiting));
+            }
+        }
+    }
+
+    /// Run every node on its own thread, values being sent over channels.
+    ///
+    /// Outputs are the same as with `run` unless a node receives values from
+    /// several others, as their interleaving then depends on thread scheduling.
+    /// Values not read yet are queued back on the inputs of the nodes, even on error.
+    pub fn run_threaded(&mut self) -> Result<NetworkState, NetworkError> {
+        let count = self.nodes.len();
+        let (senders, receivers): (Vec<Sender<W>>, Vec<Receiver<W>>) =
+            (0..count).map(|_| channel()).unzip();
+
+        let shared = &Mutex::new(Shared {
+            blocked: vec![false; count],
+            pending: vec![0; count],
+            halted: self.nodes.iter().map(|node| node.halted).collect(),
+            stop: false,
+        });
+
+        for (id, (node, sender)) in self.nodes.iter_mut().zip(&senders).enumerate() {
+            lock(shared).pending[id] = node.input.len();
+
+            for value in node.input.drain(..) {
+                sender.send(value).expect("receiver should be alive");
+            }
+        }
+
+        let mut receivers = receivers.into_iter().map(Some).collect::<Vec<_>>();
+
+        let results = thread::scope(|scope| {
+            let handles = self
+                .nodes
+                .iter_mut()
+                .zip(receivers.iter_mut())
+                .enumerate()
+                .filter(|(_, (node, _))| !node.halted)
+                .map(|(id, (node, receiver))| {
+                    let receiver = receiver.take().expect("receiver should be unused");
+                    let mut input = ThreadInput {
+                        node: id,
+                        receiver,
+                        shared,
+                    };
+                    let mut output = ThreadOutput {
+                        targets: node.targets.clone(),
+                        senders: senders.clone(),
+                        shared,
+                    };
+
+                    scope.spawn(move || {
+                        let result = loop {
+                            let result =
+                                node.machine.run_with(false, false, &mut input, &mut output);
+
+                            match result {
+                                Ok(MachineReturn::Exit(_)) => {
+                                    let mut shared = lock(shared);
+
+                                    shared.halted[id] = true;
+                                    node.halted = true;
+                                    break Ok(());
+                                }
+                                Ok(MachineReturn::NeedsInput) => break Ok(()),
+                                Ok(_) => {}
+                                Err(error) => {
+                                    let mut shared = lock(shared);
+
+                                    shared.halted[id] = true;
+                                    shared.stop = true;
+                                    break Err(NetworkError { node: id, error });
+                                }
+                            }
+                        };
+
+                        (id, result, input.receiver)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("network thread panicked"))
+                .collect::<Vec<(NodeId, Result<(), NetworkError>, Receiver<W>)>>()
+        });
+
+        let mut error = None;
+
+        for (id, result, receiver) in results {
+            receivers[id] = Some(receiver);
+            error = error.or(result.err());
+        }
+
+        for (node, receiver) in self.nodes.iter_mut().zip(receivers) {
+            if let Some(receiver) = receiver {
+                node.input.extend(receiver.try_iter());
+            }
+        }
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let waiting = (0..count)
+            .filter(|node| !self.nodes[*node].halted)
+            .collect::<Vec<NodeId>>();
+
+        if waiting.is_empty() {
+            Ok(NetworkState::Halted)
+        } else {
+            Ok(NetworkState::Deadlock(waiting))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Network, NetworkError, NetworkState};
+    use crate::{IntMachine, MachineError};
+
+    /// Read a value, output it plus one and halt.
+    fn increment() -> IntMachine {
+        IntMachine::new(vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0])
+    }
+
+    #[test]
+    pub fn test_chain_and_fan_out() {
+        let mut network = Network::new();
+
+        let first = network.add_node(increment());
+        let left = network.add_node(increment());
+        let right = network.add_node(increment());
+
+        network.connect(first, left);
+        network.connect(first, right);
+        network.push_input(first, 1);
+
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.outputs(left), &[3]);
+        assert_eq!(network.outputs(right), &[3]);
+    }
+
+    #[test]
+    pub fn test_fan_in() {
+        // Output the sum of two inputs.
+        let adder = IntMachine::new(vec![3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99, 0, 0]);
+
+        let mut network = Network::new();
+
+        let sum = network.add_node(adder);
+        let left = network.add_node(increment());
+        let right = network.add_node(increment());
+
+        network.connect(left, sum);
+        network.connect(right, sum);
+        network.push_input(left, 10);
+        network.push_input(right, 20);
+
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.outputs(sum), &[32]);
+    }
+
+    #[test]
+    pub fn test_ring() {
+        let code = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+
+        let mut network = Network::new();
+
+        for phase in &[9, 8, 7, 6, 5] {
+            let node = network.add_node(IntMachine::new(code.clone()));
+            network.push_input(node, *phase);
+        }
+
+        for node in 0..5 {
+            network.connect(node, (node + 1) % 5);
+        }
+
+        network.push_input(0, 0);
+
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.outputs(4).last(), Some(&139629729));
+    }
+
+    #[test]
+    pub fn test_threaded() {
+        let code = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+
+        let build = || {
+            let mut network = Network::new();
+
+            for phase in &[9, 7, 8, 5, 6] {
+                let node = network.add_node(IntMachine::new(code.clone()));
+                network.push_input(node, *phase);
+            }
+
+            for node in 0..5 {
+                network.connect(node, (node + 1) % 5);
+            }
+
+            let left = network.add_node(increment());
+            let right = network.add_node(increment());
+
+            network.connect(4, left);
+            network.connect(left, right);
+            network.push_input(0, 0);
+            network
+        };
+
+        let mut sequential = build();
+        let mut threaded = build();
+
+        assert_eq!(sequential.run(), Ok(NetworkState::Halted));
+        assert_eq!(threaded.run_threaded(), Ok(NetworkState::Halted));
+
+        for node in 0..sequential.len() {
+            assert_eq!(sequential.outputs(node), threaded.outputs(node));
+        }
+
+        let mut network = Network::new();
+
+        let first = network.add_node(increment());
+        let second = network.add_node(increment());
+        let third = network.add_node(IntMachine::new(vec![104, 1, 99]));
+
+        network.connect(first, second);
+        network.connect(second, first);
+
+        assert_eq!(
+            network.run_threaded(),
+            Ok(NetworkState::Deadlock(vec![first, second]))
+        );
+        assert_eq!(network.outputs(third), &[1]);
+
+        network.connect(third, first);
+        network.push_input(second, 5);
+        assert_eq!(network.run_threaded(), Ok(NetworkState::Halted));
+        assert_eq!(network.outputs(first), &[7]);
+
+        let mut network = Network::new();
+        network.add_node(increment());
+        network.add_node(IntMachine::new(vec![42]));
+
+        assert_eq!(
+            network.run_threaded(),
+            Err(NetworkError {
+                node: 1,
+                error: MachineError::InvalidInstruction(42)
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_threaded_queues() {
+        let build = || {
+            let mut network = Network::new();
+
+            let first = network.add_node(increment());
+            let halted = network.add_node(IntMachine::new(vec![99]));
+            network.add_node(IntMachine::new(vec![42]));
+
+            network.connect(first, halted);
+            network.push_input(first, 1);
+            network.push_input(first, 2);
+            network
+        };
+
+        let mut sequential = build();
+        let mut threaded = build();
+
+        let error = Err(NetworkError {
+            node: 2,
+            error: MachineError::InvalidInstruction(42),
+        });
+
+        assert_eq!(sequential.run(), error);
+        assert_eq!(threaded.run_threaded(), error);
+
+        for network in &[sequential, threaded] {
+            assert_eq!(network.nodes[0].input, &[2]);
+            assert_eq!(network.nodes[1].input, &[2]);
+        }
+    }
+
+    #[test]
+    pub fn test_deadlock_and_errors() {
+        let mut network = Network::new();
+
+        let first = network.add_node(increment());
+        let second = network.add_node(increment());
+
+        network.connect(first, second);
+        network.connect(second, first);
+
+        assert_eq!(
+            network.run(),
+            Ok(NetworkState::Deadlock(vec![first, second]))
+        );
+
+        network.push_input(second, 5);
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.outputs(first), &[7]);
+
+        let mut network = Network::new();
+        network.add_node(increment());
+        network.add_node(IntMachine::new(vec![42]));
+
+        assert_eq!(
+            network.run(),
+            Err(NetworkError {
+                node: 1,
+                error: MachineError::InvalidInstruction(42)
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_outputs_before_error() {
+        for threaded in [false, true] {
+            let mut network = Network::new();
+
+            // Output 5, then fail on an invalid instruction.
+            let first = network.add_node(IntMachine::new(vec![104, 5, 42]));
+            let second = network.add_node(increment());
+
+            network.connect(first, second);
+
+            let result = match threaded {
+                true => network.run_threaded(),
+                false => network.run(),
+            };
+
+            assert_eq!(
+                result,
+                Err(NetworkError {
+                    node: first,
+                    error: MachineError::InvalidInstruction(42)
+                })
+            );
+            assert_eq!(network.outputs(first), &[5]);
+            assert_eq!(network.outputs(second), &[6]);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::word::Word;
+use crate::IntMachineType;
+
+/// Invalid token found while parsing an Intcode program.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    /// Line of the token, starting at 1.
+    pub line: usize,
+    /// Column of the first character of the token, starting at 1.
+    pub column: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: invalid token `{}`",
+            self.line, self.column, self.token
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parse a comma separated Intcode program.
+///
+/// Whitespace around values, blank lines and trailing commas are ignored.
+pub fn parse_code(text: &str) -> Result<Vec<IntMachineType>, ParseError> {
+    parse_words(text)
+}
+
+/// Parse a comma separated Intcode program into any word type.
+///
+/// Values that don't fit in `W` are reported as invalid tokens.
+pub fn parse_words<W: Word>(text: &str) -> Result<Vec<W>, ParseError> {
+    let mut result = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let fields = line.split(',').collect::<Vec<&str>>();
+
+        let used_fields = fields
+            .iter()
+            .rposition(|field| !field.trim().is_empty())
+            .map_or(0, |index| index + 1);
+
+        let mut column = 1;
+
+        for field in &fields[..used_fields] {
+            let token = field.trim();
+            let token_column = column + field.chars().count() - field.trim_start().chars().count();
+
+            match token.parse::<W>() {
+                Ok(value) => result.push(value),
+                Err(_) => {
+                    return Err(ParseError {
+                        line: line_index + 1,
+                        column: token_column,
+                        token: token.to_string(),
+                    })
+                }
+            }
+
+            column += field.chars().count() + 1;
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_code, parse_words, ParseError};
+
+    #[test]
+    pub fn test_parse_code() {
+        assert_eq!(parse_code("1,0,0,0,99"), Ok(vec![1, 0, 0, 0, 99]));
+        assert_eq!(
+            parse_code(" 1, -2 ,3,\n\n4,5,,\r\n"),
+            Ok(vec![1, -2, 3, 4, 5])
+        );
+        assert_eq!(parse_code(""), Ok(vec![]));
+    }
+
+    #[test]
+    pub fn test_parse_errors() {
+        assert_eq!(
+            parse_code("1,2,3\n4, x5 ,6"),
+            Err(ParseError {
+                line: 2,
+                column: 4,
+                token: "x5".to_string()
+            })
+        );
+        assert_eq!(
+            parse_code("1,,2"),
+            Err(ParseError {
+                line: 1,
+                column: 3,
+                token: "".to_string()
+            })
+        );
+        assert_eq!(
+            parse_code("99999999999999999999").unwrap_err().to_string(),
+            "line 1, column 1: invalid token `99999999999999999999`"
+        );
+        assert_eq!(
+            parse_words::<i128>("99999999999999999999"),
+            Ok(vec![99999999999999999999])
+        );
+        assert_eq!(parse_words::<i32>("1,3000000000").unwrap_err().column, 3);
+    }
+}
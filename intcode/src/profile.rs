@@ -0,0 +1,330 @@
+//! Execution profiling of Intcode programs.
+//!
+//! Intcode has no call instruction, so folded stacks treat the relative base as a
+//! stack pointer: increasing it enters a frame named after the `arb` instruction,
+//! decreasing it leaves the frame.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, Write};
+
+use crate::instruction::InstructionType;
+use crate::trace::{TraceEvent, Tracer};
+use crate::word::Word;
+
+/// Tracer counting what a machine executes, the final Exit instruction included.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    steps: u64,
+    /// Executions per address and instruction, self-modifying code running several at an address.
+    by_ip: HashMap<(usize, InstructionType), u64>,
+    by_instruction: HashMap<InstructionType, u64>,
+    reads: HashMap<usize, u64>,
+    writes: HashMap<usize, u64>,
+    /// Frames entered so far, as their parent frame and the address of their `arb`.
+    frames: Vec<(Option<usize>, usize)>,
+    frame_ids: HashMap<(Option<usize>, usize), usize>,
+    /// Current frame, `None` outside of any.
+    frame: Option<usize>,
+    folded: HashMap<(Option<usize>, usize, InstructionType), u64>,
+}
+
+/// Entries sorted by decreasing count, then by key.
+fn sorted<K: Ord + Copy, V: Copy>(map: &HashMap<K, V>, count: impl Fn(&V) -> u64) -> Vec<(K, V)> {
+    let mut result = map
+        .iter()
+        .map(|(key, value)| (*key, *value))
+        .collect::<Vec<(K, V)>>();
+
+    result.sort_by(|(key_a, value_a), (key_b, value_b)| {
+        count(value_b).cmp(&count(value_a)).then(key_a.cmp(key_b))
+    });
+    result
+}
+
+fn increment<K: Eq + Hash>(map: &mut HashMap<K, u64>, key: K) {
+    *map.entry(key).or_insert(0) += 1;
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Executions per instruction address, the most executed first.
+    pub fn hot_addresses(&self) -> Vec<(usize, InstructionType, u64)> {
+        let mut result = self
+            .by_ip
+            .iter()
+            .map(|((ip, instruction), count)| (*ip, *instruction, *count))
+            .collect::<Vec<(usize, InstructionType, u64)>>();
+
+        result.sort_by_key(|(ip, instruction, count)| (Reverse(*count), *ip, instruction.opcode()));
+        result
+    }
+
+    /// Executions per instruction type, the most executed first.
+    pub fn instructions(&self) -> Vec<(InstructionType, u64)> {
+        let mut result = self
+            .by_instruction
+            .iter()
+            .map(|(instruction, count)| (*instruction, *count))
+            .collect::<Vec<(InstructionType, u64)>>();
+
+        result.sort_by_key(|(instruction, count)| (Reverse(*count), instruction.opcode()));
+        result
+    }
+
+    /// Memory reads per address in position or relative mode, the most read first.
+    pub fn reads(&self) -> Vec<(usize, u64)> {
+        sorted(&self.reads, |count| *count)
+    }
+
+    /// Memory writes per address, the most written first.
+    pub fn writes(&self) -> Vec<(usize, u64)> {
+        sorted(&self.writes, |count| *count)
+    }
+
+    /// Format the counters, each table being cut after `limit` rows.
+    pub fn report(&self, limit: usize) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+        let mut lines = vec![format!("steps: {}", self.steps), String::new()];
+
+        lines.push("instructions:".to_string());
+        for (instruction, count) in self.instructions().into_iter().take(limit) {
+            lines.push(format!(
+                "  {:<5} {:>12} {:>6.2}%",
+                instruction.mnemonic(),
+                count,
+                percent(count)
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("hot addresses:".to_string());
+        for (ip, instruction, count) in self.hot_addresses().into_iter().take(limit) {
+            lines.push(format!(
+                "  {:>5} {:<5} {:>12} {:>6.2}%",
+                ip,
+                instruction.mnemonic(),
+                count,
+                percent(count)
+            ));
+        }
+
+        for (title, counts) in &[("reads:", self.reads()), ("writes:", self.writes())] {
+            lines.push(String::new());
+            lines.push(title.to_string());
+
+            for (address, count) in counts.iter().take(limit) {
+                lines.push(format!("  {:>5} {:>12}", address, count));
+            }
+        }
+
+        let mut result = lines.join("\n");
+        result.push('\n');
+        result
+    }
+
+    /// Write one `main;arb@<ip>;...;<mnemonic>@<ip> <count>` line per stack, as read by
+    /// flamegraph tools.
+    pub fn write_folded<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut lines = self
+            .folded
+            .iter()
+            .map(|((frame, ip, instruction), count)| {
+                let mut frames = vec![format!("{}@{}", instruction.mnemonic(), ip)];
+                let mut current = *frame;
+
+                while let Some(frame) = current {
+                    let (parent, address) = self.frames[frame];
+
+                    frames.push(format!("arb@{}", address));
+                    current = parent;
+                }
+
+                frames.push("main".to_string());
+                frames.reverse();
+
+                format!("{} {}", frames.join(";"), count)
+            })
+            .collect::<Vec<String>>();
+
+        lines.sort();
+
+        for line in lines {
+            writeln!(writer, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Word> Tracer<W> for Profiler {
+    fn trace(&mut self, event: &TraceEvent<W>) -> io::Result<()> {
+        self.steps += 1;
+        increment(&mut self.by_ip, (event.ip, event.instruction));
+        increment(&mut self.by_instruction, event.instruction);
+
+        for read in &event.reads {
+            increment(&mut self.reads, read.address);
+        }
+
+        for write in &event.writes {
+            increment(&mut self.writes, write.address);
+        }
+
+        increment(&mut self.folded, (self.frame, event.ip, event.instruction));
+
+        if event.instruction == InstructionType::AdjustRelativeBase {
+            match event.values.first() {
+                Some(offset) if offset.is_negative() => {
+                    self.frame = self.frame.and_then(|frame| self.frames[frame].0);
+                }
+                Some(offset) if *offset != W::zero() => {
+                    let frames = &mut self.frames;
+                    let key = (self.frame, event.ip);
+
+                    self.frame = Some(*self.frame_ids.entry(key).or_insert_with(|| {
+                        frames.push(key);
+                        frames.len() - 1
+                    }));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::Profiler;
+    use crate::network::{Network, NetworkState};
+    use crate::{InstructionType, IntMachine};
+
+    #[test]
+    pub fn test_counters() {
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        // Counts down from 3 at address 8.
+        let mut machine = IntMachine::new(vec![1001, 8, -1, 8, 1005, 8, 0, 99, 3]);
+
+        machine.set_tracer(Some(Box::new(profiler.clone())));
+        machine.run(false, false, vec![]).unwrap();
+
+        let profiler = profiler.lock().unwrap();
+
+        assert_eq!(profiler.steps(), 7);
+        assert_eq!(
+            profiler.instructions(),
+            vec![
+                (InstructionType::Addition, 3),
+                (InstructionType::JumpIfTrue, 3),
+                (InstructionType::Exit, 1)
+            ]
+        );
+        assert_eq!(
+            profiler.hot_addresses(),
+            vec![
+                (0, InstructionType::Addition, 3),
+                (4, InstructionType::JumpIfTrue, 3),
+                (7, InstructionType::Exit, 1)
+            ]
+        );
+        assert_eq!(profiler.reads(), vec![(8, 6)]);
+        assert_eq!(profiler.writes(), vec![(8, 3)]);
+
+        let report = profiler.report(1);
+        assert!(report.starts_with("steps: 7\n\ninstructions:\n  add              3  42.86%\n"));
+        assert!(report.contains("hot addresses:\n      0 add              3  42.86%\n\n"));
+    }
+
+    #[test]
+    pub fn test_self_modifying() {
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        // Turns the addition at 0 into an Exit, then jumps back to it.
+        let mut machine = IntMachine::new(vec![1101, 1, 1, 20, 1101, 0, 99, 0, 1105, 1, 0]);
+
+        machine.set_tracer(Some(Box::new(profiler.clone())));
+        machine.run(false, false, vec![]).unwrap();
+
+        let profiler = profiler.lock().unwrap();
+
+        assert_eq!(
+            profiler.hot_addresses(),
+            vec![
+                (0, InstructionType::Addition, 1),
+                (0, InstructionType::Exit, 1),
+                (4, InstructionType::Addition, 1),
+                (8, InstructionType::JumpIfTrue, 1)
+            ]
+        );
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main;add@0 1\nmain;add@4 1\nmain;hlt@0 1\nmain;jt@8 1\n"
+        );
+    }
+
+    #[test]
+    pub fn test_folded() {
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        // Enters a frame at 2 and leaves it at 6.
+        let mut machine = IntMachine::new(vec![104, 1, 109, 10, 204, -10, 109, -10, 99]);
+
+        machine.set_tracer(Some(Box::new(profiler.clone())));
+        machine.run(false, false, vec![]).unwrap();
+
+        let mut folded = Vec::new();
+        profiler.lock().unwrap().write_folded(&mut folded).unwrap();
+
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main;arb@2 1\nmain;arb@2;arb@6 1\nmain;arb@2;out@4 1\nmain;hlt@8 1\nmain;out@0 1\n"
+        );
+    }
+
+    #[test]
+    pub fn test_feedback_loop() {
+        // Every amplifier of a feedback loop reports into the same profiler.
+        let code = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let profiler = Arc::new(Mutex::new(Profiler::new()));
+        let mut network = Network::new();
+
+        for phase in 5..10 {
+            let node = network.add_node(IntMachine::new(code.clone()));
+
+            network
+                .machine_mut(node)
+                .set_tracer(Some(Box::new(profiler.clone())));
+            network.push_input(node, phase);
+        }
+
+        for node in 0..5 {
+            network.connect(node, (node + 1) % 5);
+        }
+
+        network.push_input(0, 0);
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+
+        let profiler = profiler.lock().unwrap();
+
+        // Each amplifier loops 5 times over the 6 instructions from address 6.
+        assert_eq!(profiler.hot_addresses()[0], (6, InstructionType::Input, 25));
+        assert_eq!(profiler.steps(), 5 * (2 + 5 * 6 + 1));
+    }
+}
@@ -0,0 +1,199 @@
+//! Detection of programs writing over their own instructions.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+
+use crate::trace::{TraceEvent, Tracer};
+use crate::word::Word;
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ModificationKind {
+    /// The address was executed before being written.
+    AlreadyExecuted,
+    /// The address was written, then executed.
+    ExecutedLater,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SelfModification<W = IntMachineType> {
+    /// Step of the writing instruction.
+    pub step: u64,
+    pub writer_ip: usize,
+    pub address: usize,
+    pub old_value: W,
+    pub new_value: W,
+    pub kind: ModificationKind,
+}
+
+impl<W: fmt::Display> fmt::Display for SelfModification<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let when = match self.kind {
+            ModificationKind::AlreadyExecuted => "already executed",
+            ModificationKind::ExecutedLater => "executed later",
+        };
+
+        write!(
+            f,
+            "step {}: ip {} wrote {} over {} at {} ({})",
+            self.step, self.writer_ip, self.new_value, self.old_value, self.address, when
+        )
+    }
+}
+
+/// Tracer reporting writes into addresses executed as part of an instruction, before or after the write.
+#[derive(Debug)]
+pub struct SelfModificationDetector<W = IntMachineType> {
+    executed: HashSet<usize>,
+    /// Last write into each address not executed yet, reported if it ever is.
+    pending: HashMap<usize, SelfModification<W>>,
+    modifications: Vec<SelfModification<W>>,
+}
+
+impl<W> Default for SelfModificationDetector<W> {
+    fn default() -> Self {
+        SelfModificationDetector {
+            executed: HashSet::new(),
+            pending: HashMap::new(),
+            modifications: Vec::new(),
+        }
+    }
+}
+
+impl<W> SelfModificationDetector<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Modifications in the order they were detected.
+    pub fn modifications(&self) -> &[SelfModification<W>] {
+        &self.modifications
+    }
+}
+
+impl<W: Word> Tracer<W> for SelfModificationDetector<W> {
+    fn trace(&mut self, event: &TraceEvent<W>) -> io::Result<()> {
+        for address in event.ip..event.ip + event.instruction.code_size() {
+            if self.executed.insert(address) {
+                if let Some(modification) = self.pending.remove(&address) {
+                    self.modifications.push(modification);
+                }
+            }
+        }
+
+        for write in &event.writes {
+            let kind = match self.executed.contains(&write.address) {
+                true => ModificationKind::AlreadyExecuted,
+                false => ModificationKind::ExecutedLater,
+            };
+            let modification = SelfModification {
+                step: event.step,
+                writer_ip: event.ip,
+                address: write.address,
+                old_value: write.old_value.clone(),
+                new_value: write.new_value.clone(),
+                kind,
+            };
+
+            match kind {
+                ModificationKind::AlreadyExecuted => self.modifications.push(modification),
+                ModificationKind::ExecutedLater => {
+                    self.pending.insert(write.address, modification);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{ModificationKind, SelfModification, SelfModificationDetector};
+    use crate::IntMachine;
+
+    fn detect(code: Vec<i64>) -> Vec<SelfModification> {
+        let detector = Arc::new(Mutex::new(SelfModificationDetector::new()));
+        let mut machine = IntMachine::new(code);
+
+        machine.set_tracer(Some(Box::new(detector.clone())));
+        machine.run(false, false, vec![]).unwrap();
+
+        let modifications = detector.lock().unwrap().modifications().to_vec();
+        modifications
+    }
+
+    #[test]
+    pub fn test_executed_later() {
+        let modifications = detect(vec![1002, 4, 3, 4, 33]);
+
+        assert_eq!(
+            modifications,
+            vec![SelfModification {
+                step: 0,
+                writer_ip: 0,
+                address: 4,
+                old_value: 33,
+                new_value: 99,
+                kind: ModificationKind::ExecutedLater,
+            }]
+        );
+        assert_eq!(
+            modifications[0].to_string(),
+            "step 0: ip 0 wrote 99 over 33 at 4 (executed later)"
+        );
+
+        // Writing data that never runs isn't reported.
+        assert!(detect(vec![1101, 1, 2, 5, 99, 0]).is_empty());
+    }
+
+    #[test]
+    pub fn test_last_write_executed_later() {
+        // Writes 2, then 99 into the Exit instruction it jumps to.
+        let modifications = detect(vec![1101, 1, 1, 11, 1101, 90, 9, 11, 1105, 1, 11, 0]);
+
+        assert_eq!(
+            modifications,
+            vec![SelfModification {
+                step: 1,
+                writer_ip: 4,
+                address: 11,
+                old_value: 2,
+                new_value: 99,
+                kind: ModificationKind::ExecutedLater,
+            }]
+        );
+
+        // Counts a data cell down from 200000, the writes to it must not pile up.
+        let detector = Arc::new(Mutex::new(SelfModificationDetector::new()));
+        let mut machine = IntMachine::new(vec![1001, 8, -1, 8, 1005, 8, 0, 99, 200000]);
+
+        machine.set_tracer(Some(Box::new(detector.clone())));
+        machine.run(false, false, vec![]).unwrap();
+
+        let detector = detector.lock().unwrap();
+
+        assert!(detector.modifications().is_empty());
+        assert_eq!(detector.pending.len(), 1);
+        assert_eq!(detector.pending[&8].new_value, 0);
+    }
+
+    #[test]
+    pub fn test_already_executed() {
+        // Increments the immediate operand of its own Output instruction.
+        let modifications = detect(vec![104, 1, 1001, 1, 1, 1, 1007, 1, 3, 20, 1005, 20, 0, 99]);
+
+        assert_eq!(modifications.len(), 2);
+        assert!(modifications.iter().all(|modification| modification.kind
+            == ModificationKind::AlreadyExecuted
+            && modification.writer_ip == 2
+            && modification.address == 1));
+        assert_eq!(
+            (modifications[1].old_value, modifications[1].new_value),
+            (2, 3)
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Saving and restoring the full state of an `IntMachine`.
+//!
+//! Snapshots are saved as text, one `key value` pair per line:
+//!
+//! ```text
+//! intcode-snapshot 1
+//! ip 2
+//! relative_base 0
+//! steps 1
+//! outputs 7
+//! memory linear
+//! limit 16777216
+//! len 4
+//! ram 104,7,3,0
+//! ```
+//!
+//! A paged memory replaces the `ram` line with one `page <index> <values>`
+//! line per allocated page, trailing zeros of a page being omitted.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::memory::{Memory, PAGE_SIZE};
+use crate::word::Word;
+use crate::IntMachineType;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// State of an `IntMachine` at a given point of its execution.
+///
+/// The tracer and the overflow policy are configuration, not state, and aren't part of it.
+#[derive(Debug, Clone)]
+pub struct Snapshot<W = IntMachineType> {
+    pub ip: usize,
+    pub relative_base: W,
+    pub steps: u64,
+    pub memory: Memory<W>,
+    /// Every value output so far, returned by `run` when the machine exits.
+    pub output_values: Vec<W>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SnapshotError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl SnapshotError {
+    fn new(line: usize, message: String) -> Self {
+        SnapshotError { line, message }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for SnapshotError {}
+
+fn format_values<W: Word>(values: &[W]) -> String {
+    values
+        .iter()
+        .map(W::to_string)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn parse_value<T: std::str::FromStr>(line: usize, text: &str) -> Result<T, SnapshotError> {
+    text.parse()
+        .map_err(|_| SnapshotError::new(line, format!("invalid value `{}`", text)))
+}
+
+fn parse_values<W: Word>(line: usize, text: &str) -> Result<Vec<W>, SnapshotError> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(',')
+        .map(|value| parse_value(line, value))
+        .collect()
+}
+
+impl<W: Word> Snapshot<W> {
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            HEADER.to_string(),
+            format!("ip {}", self.ip),
+            format!("relative_base {}", self.relative_base),
+            format!("steps {}", self.steps),
+            format!("outputs {}", format_values(&self.output_values)),
+        ];
+
+        let pages = self.memory.pages();
+
+        lines.push(match pages {
+            Some(_) => "memory paged".to_string(),
+            None => "memory linear".to_string(),
+        });
+        lines.push(match self.memory.limit() {
+            Some(limit) => format!("limit {}", limit),
+            None => "limit none".to_string(),
+        });
+        lines.push(format!("len {}", self.memory.len()));
+
+        match pages {
+            Some(pages) => {
+                for (index, page) in pages {
+                    let used = page
+                        .iter()
+                        .rposition(|value| *value != W::zero())
+                        .map_or(0, |position| position + 1);
+
+                    lines.push(format!("page {} {}", index, format_values(&page[..used])));
+                }
+            }
+            None => {
+                let ram = self
+                    .memory
+                    .to_vec()
+                    .expect("a linear memory always fits in a vec");
+
+                lines.push(format!("ram {}", format_values(&ram)));
+            }
+        }
+
+        let mut result = lines.join("\n");
+        result.push('\n');
+        result
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, SnapshotError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line));
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(SnapshotError::new(1, format!("expected `{}`", HEADER))),
+        }
+
+        let mut ip = None;
+        let mut relative_base = None;
+        let mut steps = None;
+        let mut output_values = None;
+        let mut paged = None;
+        let mut limit = None;
+        let mut len = None;
+        let mut ram = None;
+        let mut pages = Vec::new();
+
+        for (line, text) in lines {
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = match text.find(' ') {
+                Some(index) => (&text[..index], text[index + 1..].trim()),
+                None => (text.trim(), ""),
+            };
+
+            match key {
+                "ip" => ip = Some(parse_value(line, value)?),
+                "relative_base" => relative_base = Some(parse_value(line, value)?),
+                "steps" => steps = Some(parse_value(line, value)?),
+                "outputs" => output_values = Some(parse_values(line, value)?),
+                "memory" => match value {
+                    "linear" => paged = Some(false),
+                    "paged" => paged = Some(true),
+                    _ => {
+                        return Err(SnapshotError::new(
+                            line,
+                            format!("unknown memory kind `{}`", value),
+                        ))
+                    }
+                },
+                "limit" => {
+                    limit = match value {
+                        "none" => Some(None),
+                        _ => Some(Some(parse_value(line, value)?)),
+                    }
+                }
+                "len" => len = Some(parse_value(line, value)?),
+                "ram" => ram = Some(parse_values(line, value)?),
+                "page" => {
+                    let (index, values) = match value.find(' ') {
+                        Some(position) => (&value[..position], &value[position + 1..]),
+                        None => (value, ""),
+                    };
+                    let values = parse_values::<W>(line, values)?;
+
+                    if values.len() > PAGE_SIZE {
+                        return Err(SnapshotError::new(
+                            line,
+                            format!("page larger than {} words", PAGE_SIZE),
+                        ));
+                    }
+
+                    pages.push((parse_value(line, index)?, values));
+                }
+                _ => return Err(SnapshotError::new(line, format!("unknown key `{}`", key))),
+            }
+        }
+
+        let end = text.lines().count() + 1;
+        let missing = |key: &str| SnapshotError::new(end, format!("missing `{}`", key));
+
+        let len = len.ok_or_else(|| missing("len"))?;
+        let limit = limit.ok_or_else(|| missing("limit"))?;
+
+        let memory = if paged.ok_or_else(|| missing("memory"))? {
+            Memory::from_pages(pages, len)
+        } else {
+            let ram = ram.ok_or_else(|| missing("ram"))?;
+
+            if ram.len() != len {
+                return Err(SnapshotError::new(
+                    end,
+                    format!("`ram` holds {} words instead of {}", ram.len(), len),
+                ));
+            }
+
+            Memory::new(ram)
+        };
+
+        Ok(Snapshot {
+            ip: ip.ok_or_else(|| missing("ip"))?,
+            relative_base: relative_base.ok_or_else(|| missing("relative_base"))?,
+            steps: steps.ok_or_else(|| missing("steps"))?,
+            memory: memory.with_limit(limit),
+            output_values: output_values.ok_or_else(|| missing("outputs"))?,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Read a snapshot saved by `save`, invalid files being reported as `InvalidData` errors.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+
+        Snapshot::from_text(&text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Snapshot, SnapshotError};
+    use crate::{IntMachine, MachineReturn, Memory, PAGE_SIZE};
+
+    #[test]
+    pub fn test_snapshot_restore() {
+        let mut machine = IntMachine::new(vec![104, 7, 3, 0, 4, 0, 99]);
+
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::NeedsInput)
+        );
+
+        let snapshot = machine.snapshot();
+
+        assert_eq!(
+            machine.run(false, false, vec![1]),
+            Ok(MachineReturn::Exit(vec![7, 1]))
+        );
+
+        machine.restore(&snapshot);
+        assert_eq!(machine.ip(), 2);
+        assert_eq!(
+            machine.run(false, false, vec![2]),
+            Ok(MachineReturn::Exit(vec![7, 2]))
+        );
+
+        let mut machine = IntMachine::from_snapshot(&snapshot);
+        assert_eq!(machine.steps(), 1);
+        assert_eq!(
+            machine.run(false, false, vec![3]),
+            Ok(MachineReturn::Exit(vec![7, 3]))
+        );
+    }
+
+    #[test]
+    pub fn test_text_format() {
+        let mut machine = IntMachine::new(vec![109, -2, 104, 7, 3, 0, 99]);
+        machine.run(false, false, vec![]).unwrap();
+
+        let text = machine.snapshot().to_text();
+
+        assert_eq!(
+            text,
+            "intcode-snapshot 1\nip 4\nrelative_base -2\nsteps 2\noutputs 7\nmemory linear\n\
+             limit 16777216\nlen 7\nram 109,-2,104,7,3,0,99\n"
+        );
+
+        let snapshot = Snapshot::<i64>::from_text(&text).unwrap();
+        assert_eq!(snapshot.to_text(), text);
+
+        let mut memory = Memory::paged(vec![1, 2, 3]);
+        memory.write(5 * PAGE_SIZE + 1, 4).unwrap();
+
+        let snapshot = IntMachine::with_memory(memory.with_limit(Some(1 << 20))).snapshot();
+        let text = snapshot.to_text();
+
+        assert!(text.ends_with("limit 1048576\nlen 5122\npage 0 1,2,3\npage 5 0,4\n"));
+
+        let restored = Snapshot::<i64>::from_text(&text).unwrap();
+        assert_eq!(restored.memory.read(5 * PAGE_SIZE + 1), Ok(4));
+        assert_eq!(restored.memory.len(), 5 * PAGE_SIZE + 2);
+        assert_eq!(restored.memory.limit(), Some(1 << 20));
+        assert_eq!(restored.to_text(), text);
+
+        assert_eq!(
+            Snapshot::<i64>::from_text("intcode-snapshot 1\nip x\n").unwrap_err(),
+            SnapshotError::new(2, "invalid value `x`".to_string())
+        );
+        assert_eq!(
+            Snapshot::<i64>::from_text("intcode-snapshot 1\nip 0\n")
+                .unwrap_err()
+                .message,
+            "missing `len`"
+        );
+    }
+}
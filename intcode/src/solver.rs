@@ -0,0 +1,528 @@
+//! Searching the values to patch into a program for it to compute a given result.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+use crate::disasm::Operand;
+use crate::instruction::InstructionType;
+use crate::machine::{IntMachine, MachineReturn};
+use crate::memory::Memory;
+use crate::snapshot::Snapshot;
+use crate::symbolic::{Expr, Symbolic};
+use crate::trace::{TraceEvent, Tracer};
+use crate::word::convert_code;
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct NoSolution;
+
+impl fmt::Display for NoSolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no patched values produce the target")
+    }
+}
+
+impl Error for NoSolution {}
+
+/// `evaluate` was given a number of values other than the number of patches.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WrongValueCount {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for WrongValueCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected {} patched values, found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for WrongValueCount {}
+
+/// Result of the program as `constant + sum(coefficient * value)` of the patched values.
+struct LinearModel {
+    constant: i128,
+    coefficients: Vec<i128>,
+    /// Smallest and largest sums of the terms following each patch, over their ranges.
+    rest_bounds: Vec<(i128, i128)>,
+}
+
+/// Largest magnitude of a model, keeping every partial sum of the search far from overflowing.
+const MAX_MODEL_MAGNITUDE: i128 = 1 << 120;
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    match a % b != 0 && (a < 0) != (b < 0) {
+        true => a / b - 1,
+        false => a / b,
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    match a % b != 0 && (a < 0) == (b < 0) {
+        true => a / b + 1,
+        false => a / b,
+    }
+}
+
+/// Whether an expression only adds variables and multiplies them by constants.
+fn is_affine(expr: &Arc<Expr>) -> bool {
+    // Expressions share their subexpressions, each one is checked once.
+    let mut visited = HashSet::new();
+    let mut pending = vec![expr];
+
+    while let Some(expr) = pending.pop() {
+        if !visited.insert(Arc::as_ptr(expr)) {
+            continue;
+        }
+
+        match &**expr {
+            Expr::Constant(_) | Expr::Variable(_) => {}
+            Expr::Add(left, right) => {
+                pending.push(left);
+                pending.push(right);
+            }
+            Expr::Mul(factor, expr) | Expr::Mul(expr, factor) if factor.constant().is_some() => {
+                pending.push(expr)
+            }
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/// Tracer noticing when a value computed from the patched ones is used as an opcode,
+/// an address, a jump condition or target, or a relative base offset.
+#[derive(Default)]
+struct PathCheck {
+    dependent: bool,
+}
+
+impl Tracer<Symbolic> for PathCheck {
+    fn trace(&mut self, event: &TraceEvent<Symbolic>) -> io::Result<()> {
+        let addresses = event.operands.iter().filter_map(|operand| match operand {
+            Operand::Position(address) | Operand::Relative(address) => Some(address),
+            Operand::Immediate(_) => None,
+        });
+        let controls = match event.instruction {
+            InstructionType::JumpIfTrue
+            | InstructionType::JumpIfFalse
+            | InstructionType::AdjustRelativeBase => &event.values[..],
+            _ => &[],
+        };
+
+        self.dependent |= !event.opcode.is_constant()
+            || addresses.chain(controls).any(|value| !value.is_constant());
+        Ok(())
+    }
+}
+
+/// Call `visit` on every combination of values in lexicographic order, until it returns false.
+fn for_each_combination(
+    ranges: &[RangeInclusive<IntMachineType>],
+    values: &mut Vec<IntMachineType>,
+    visit: &mut dyn FnMut(&[IntMachineType]) -> bool,
+) -> bool {
+    match ranges.split_first() {
+        None => visit(values),
+        Some((range, rest)) => {
+            for value in range.clone() {
+                values.push(value);
+
+                let carry_on = for_each_combination(rest, values, visit);
+
+                values.pop();
+
+                if !carry_on {
+                    return false;
+                }
+            }
+
+            true
+        }
+    }
+}
+
+/// Search the values to write at some addresses of a program so that, once halted,
+/// it holds `target` at `output_address`.
+///
+/// A patched program failing, waiting for input or running out of its step budget isn't a solution.
+pub struct GoalSeeker {
+    start: Snapshot,
+    patches: Vec<(usize, RangeInclusive<IntMachineType>)>,
+    output_address: usize,
+    target: IntMachineType,
+    step_budget: Option<u64>,
+}
+
+impl GoalSeeker {
+    pub fn new(code: Vec<IntMachineType>, output_address: usize, target: IntMachineType) -> Self {
+        GoalSeeker {
+            start: IntMachine::new(code).snapshot(),
+            patches: Vec::new(),
+            output_address,
+            target,
+            step_budget: None,
+        }
+    }
+
+    /// Try every value of `range` at `address`, solutions listing values in patch order.
+    pub fn add_patch(&mut self, address: usize, range: RangeInclusive<IntMachineType>) {
+        self.patches.push((address, range));
+    }
+
+    /// Set the number of instructions allowed to each patched program.
+    pub fn set_step_budget(&mut self, step_budget: Option<u64>) {
+        self.step_budget = step_budget;
+    }
+
+    /// Run the program patched with one value per patch, returning the value at the output
+    /// address, `None` if the program doesn't halt.
+    pub fn evaluate(
+        &self,
+        values: &[IntMachineType],
+    ) -> Result<Option<IntMachineType>, WrongValueCount> {
+        match values.len() == self.patches.len() {
+            true => Ok(self.run(values)),
+            false => Err(WrongValueCount {
+                expected: self.patches.len(),
+                found: values.len(),
+            }),
+        }
+    }
+
+    /// Return the first solution in lexicographic order, stopping the search there.
+    pub fn find_first(&self) -> Result<Vec<IntMachineType>, NoSolution> {
+        self.search(true).into_iter().next().ok_or(NoSolution)
+    }
+
+    /// Return every solution in lexicographic order.
+    pub fn find_all(&self) -> Result<Vec<Vec<IntMachineType>>, NoSolution> {
+        let solutions = self.search(false);
+
+        match solutions.is_empty() {
+            true => Err(NoSolution),
+            false => Ok(solutions),
+        }
+    }
+
+    fn run(&self, values: &[IntMachineType]) -> Option<IntMachineType> {
+        let mut machine = IntMachine::from_snapshot(&self.start);
+
+        machine.set_step_budget(self.step_budget);
+
+        for ((address, _), value) in self.patches.iter().zip(values) {
+            machine.write_memory(*address, *value).ok()?;
+        }
+
+        match machine.run(false, false, Vec::new()) {
+            Ok(MachineReturn::Exit(_)) => machine.memory().read(self.output_address).ok(),
+            _ => None,
+        }
+    }
+
+    fn ranges(&self) -> Vec<RangeInclusive<IntMachineType>> {
+        self.patches
+            .iter()
+            .map(|(_, range)| range.clone())
+            .collect()
+    }
+
+    fn search(&self, first_only: bool) -> Vec<Vec<IntMachineType>> {
+        let mut solutions = Vec::new();
+
+        if let Some(model) = self.linear_model() {
+            self.solve_linear(
+                &model,
+                model.constant,
+                &mut Vec::new(),
+                &mut solutions,
+                first_only,
+            );
+            return solutions;
+        }
+
+        for_each_combination(&self.ranges(), &mut Vec::new(), &mut |values| {
+            if self.run(values) != Some(self.target) {
+                return true;
+            }
+
+            solutions.push(values.to_vec());
+            !first_only
+        });
+
+        solutions
+    }
+
+    /// Run the program symbolically on the first value of every range, checking that it takes
+    /// the same path whatever the patched values and computes an affine function of them.
+    fn is_linear(&self) -> bool {
+        // A memory too large to dump is left to the exhaustive search.
+        let code = match self.start.memory.to_vec() {
+            Ok(code) => {
+                convert_code::<Symbolic>(&code).expect("every word converts to a symbolic one")
+            }
+            Err(_) => return false,
+        };
+        let mut machine = IntMachine::with_memory(Memory::new(code));
+        let path_check = Arc::new(Mutex::new(PathCheck::default()));
+
+        machine.set_step_budget(self.step_budget);
+        machine.set_tracer(Some(Box::new(path_check.clone())));
+
+        for (index, (address, range)) in self.patches.iter().enumerate() {
+            let value = Symbolic::variable(&format!("patch{}", index), *range.start());
+
+            if machine.write_memory(*address, value).is_err() {
+                return false;
+            }
+        }
+
+        match machine.run(false, false, Vec::<Symbolic>::new()) {
+            Ok(MachineReturn::Exit(_)) => {}
+            _ => return false,
+        }
+
+        let dependent = path_check.lock().map_or(true, |check| check.dependent);
+
+        match machine.memory().read(self.output_address) {
+            Ok(result) => !dependent && is_affine(result.expr()),
+            Err(_) => false,
+        }
+    }
+
+    /// Build the model of a program proven linear by probing it once per patch.
+    fn linear_model(&self) -> Option<LinearModel> {
+        let ranges = self.ranges();
+
+        if ranges.iter().any(|range| range.is_empty()) || !self.is_linear() {
+            return None;
+        }
+
+        let base = ranges
+            .iter()
+            .map(|range| *range.start())
+            .collect::<Vec<IntMachineType>>();
+        let base_result = i128::from(self.run(&base)?);
+
+        let mut constant = base_result;
+        let mut coefficients = Vec::new();
+
+        for (index, range) in ranges.iter().enumerate() {
+            let coefficient = match range.start() == range.end() {
+                true => 0,
+                false => {
+                    let mut probe = base.clone();
+                    probe[index] += 1;
+
+                    i128::from(self.run(&probe)?) - base_result
+                }
+            };
+
+            constant = constant.checked_sub(coefficient.checked_mul(i128::from(base[index]))?)?;
+            coefficients.push(coefficient);
+        }
+
+        let mut rest_bounds = vec![(0, 0); ranges.len()];
+        let (mut low, mut high) = (0, 0);
+        let mut magnitude = constant.checked_abs()?;
+
+        for index in (0..ranges.len()).rev() {
+            rest_bounds[index] = (low, high);
+
+            let start = coefficients[index].checked_mul(i128::from(*ranges[index].start()))?;
+            let end = coefficients[index].checked_mul(i128::from(*ranges[index].end()))?;
+
+            magnitude = magnitude.checked_add(start.checked_abs()?.max(end.checked_abs()?))?;
+
+            if magnitude > MAX_MODEL_MAGNITUDE {
+                return None;
+            }
+
+            low += start.min(end);
+            high += start.max(end);
+        }
+
+        Some(LinearModel {
+            constant,
+            coefficients,
+            rest_bounds,
+        })
+    }
+
+    /// Enumerate the values of the next patch for which the target stays reachable, `partial`
+    /// being the model evaluated on the values chosen so far.
+    ///
+    /// Every candidate is still run, as the program may overflow. Return false to stop the search.
+    fn solve_linear(
+        &self,
+        model: &LinearModel,
+        partial: i128,
+        values: &mut Vec<IntMachineType>,
+        solutions: &mut Vec<Vec<IntMachineType>>,
+        first_only: bool,
+    ) -> bool {
+        let index = values.len();
+
+        let range = match self.patches.get(index) {
+            Some((_, range)) => range,
+            None => {
+                if partial == i128::from(self.target) && self.run(values) == Some(self.target) {
+                    solutions.push(values.clone());
+                    return !first_only;
+                }
+
+                return true;
+            }
+        };
+        let (start, end) = (i128::from(*range.start()), i128::from(*range.end()));
+
+        let coefficient = model.coefficients[index];
+        let (low, high) = model.rest_bounds[index];
+        let rest = i128::from(self.target) - partial;
+
+        // The terms of this patch have to lie in `rest - high..=rest - low`.
+        let (first, last) = match coefficient {
+            0 if low <= rest && rest <= high => (start, end),
+            0 => return true,
+            _ if coefficient > 0 => (
+                div_ceil(rest - high, coefficient),
+                div_floor(rest - low, coefficient),
+            ),
+            _ => (
+                div_ceil(rest - low, coefficient),
+                div_floor(rest - high, coefficient),
+            ),
+        };
+
+        let mut value = first.max(start);
+        let last = last.min(end);
+
+        while value <= last {
+            values.push(value as IntMachineType);
+
+            let carry_on = self.solve_linear(
+                model,
+                partial + coefficient * value,
+                values,
+                solutions,
+                first_only,
+            );
+
+            values.pop();
+
+            if !carry_on {
+                return false;
+            }
+
+            value += 1;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GoalSeeker, NoSolution, WrongValueCount};
+
+    #[test]
+    pub fn test_linear() {
+        // mem[0] = 3 * mem[9] + mem[10]
+        let code = vec![1002, 9, 3, 0, 1, 0, 10, 0, 99, 0, 0];
+
+        let mut seeker = GoalSeeker::new(code.clone(), 0, 20);
+        seeker.add_patch(9, 0..=9);
+        seeker.add_patch(10, 0..=9);
+
+        assert_eq!(seeker.evaluate(&[4, 8]), Ok(Some(20)));
+        assert_eq!(
+            seeker.evaluate(&[4]),
+            Err(WrongValueCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(seeker.find_first(), Ok(vec![4, 8]));
+        assert_eq!(
+            seeker.find_all(),
+            Ok(vec![vec![4, 8], vec![5, 5], vec![6, 2]])
+        );
+
+        // Far too many combinations to try them all.
+        let mut seeker = GoalSeeker::new(code, 0, 3_000_004);
+        seeker.add_patch(9, 0..=1_000_000);
+        seeker.add_patch(10, 0..=1_000_000);
+
+        assert_eq!(seeker.find_first(), Ok(vec![666_668, 1_000_000]));
+
+        // mem[0] = -2 * mem[9] + mem[10]
+        let mut seeker = GoalSeeker::new(vec![1002, 9, -2, 0, 1, 0, 10, 0, 99, 0, 0], 0, 5);
+        seeker.add_patch(9, -1_000_000..=1_000_000);
+        seeker.add_patch(10, -1_000_000..=1_000_000);
+
+        assert_eq!(seeker.find_first(), Ok(vec![-500_002, -999_999]));
+
+        let mut seeker = GoalSeeker::new(vec![1002, 9, 3, 0, 1, 0, 10, 0, 99, 0, 0], 0, 31);
+        seeker.add_patch(9, 0..=9);
+        seeker.add_patch(10, 0..=0);
+
+        assert_eq!(seeker.find_all(), Err(NoSolution));
+    }
+
+    #[test]
+    pub fn test_brute_force() {
+        // mem[0] = mem[9] * mem[10]
+        let mut seeker = GoalSeeker::new(vec![2, 9, 10, 0, 99, 0, 0, 0, 0, 0, 0], 0, 12);
+        seeker.add_patch(9, 1..=6);
+        seeker.add_patch(10, 1..=6);
+
+        assert_eq!(
+            seeker.find_all(),
+            Ok(vec![vec![2, 6], vec![3, 4], vec![4, 3], vec![6, 2]])
+        );
+
+        // Patching opcode 0 in fails, opcode 1 adds and opcode 2 multiplies.
+        let mut seeker = GoalSeeker::new(vec![1, 5, 6, 0, 99, 2, 3], 0, 6);
+        seeker.add_patch(0, 0..=2);
+
+        assert_eq!(seeker.evaluate(&[0]), Ok(None));
+        assert_eq!(seeker.find_all(), Ok(vec![vec![2]]));
+
+        // Jump to the patched address, 0 looping forever.
+        let mut seeker = GoalSeeker::new(vec![1106, 0, 3, 0, 99], 0, 1106);
+        seeker.set_step_budget(Some(100));
+        seeker.add_patch(2, 0..=4);
+
+        assert_eq!(seeker.find_all(), Ok(vec![vec![4]]));
+
+        // mem[0] = 100 when mem[17] == 3, mem[17] + mem[18] otherwise.
+        let code = vec![
+            1008, 17, 3, 19, 1005, 19, 12, 1, 17, 18, 0, 99, 1101, 100, 0, 0, 99, 0, 0, 0,
+        ];
+        let mut seeker = GoalSeeker::new(code, 0, 100);
+        seeker.add_patch(17, 0..=9);
+        seeker.add_patch(18, 0..=9);
+
+        assert_eq!(seeker.evaluate(&[3, 0]), Ok(Some(100)));
+        assert_eq!(seeker.find_first(), Ok(vec![3, 0]));
+        assert_eq!(
+            seeker.find_all(),
+            Ok((0..=9).map(|value| vec![3, value]).collect())
+        );
+
+        // mem[0] = mem[mem[1]] + 7, the patched value being an address.
+        let mut seeker = GoalSeeker::new(vec![1, 9, 10, 0, 99, 0, 0, 0, 0, 5, 7], 0, 12);
+        seeker.add_patch(1, 0..=10);
+
+        assert_eq!(seeker.find_all(), Ok(vec![vec![9]]));
+    }
+}
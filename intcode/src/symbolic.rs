@@ -0,0 +1,739 @@
+//! Symbolic execution of Intcode programs.
+//!
+//! A `Symbolic` word pairs a concrete value with the expression it was computed from.
+//! Running an `IntMachine<Symbolic>` follows the path taken by the concrete values,
+//! Addition, Multiplication, LessThan and Equals building expressions along the way.
+//! Addresses, jump targets and the relative base only use the concrete values.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::instruction::InstructionType;
+use crate::io::Input;
+use crate::machine::MachineError;
+use crate::trace::{TraceEvent, Tracer};
+use crate::word::Word;
+use crate::IntMachineType;
+
+/// Expression computed by a program.
+///
+/// Loops can build expressions with millions of nodes, so they are compared, hashed,
+/// printed, evaluated and dropped without recursing.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Constant(IntMachineType),
+    Variable(String),
+    Add(Arc<Expr>, Arc<Expr>),
+    Mul(Arc<Expr>, Arc<Expr>),
+    /// 1 when the left side is less than the right one, 0 otherwise.
+    LessThan(Arc<Expr>, Arc<Expr>),
+    /// 1 when both sides are equal, 0 otherwise.
+    Equals(Arc<Expr>, Arc<Expr>),
+}
+
+/// Part of an expression left to print.
+enum Piece<'a> {
+    Expr(&'a Expr, bool),
+    Text(&'static str),
+    Number(i128),
+}
+
+impl Expr {
+    pub fn constant(&self) -> Option<IntMachineType> {
+        match self {
+            Expr::Constant(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn operands(&self) -> Option<(&Arc<Expr>, &Arc<Expr>)> {
+        match self {
+            Expr::Constant(_) | Expr::Variable(_) => None,
+            Expr::Add(left, right)
+            | Expr::Mul(left, right)
+            | Expr::LessThan(left, right)
+            | Expr::Equals(left, right) => Some((left, right)),
+        }
+    }
+
+    /// Split a sum into its non constant terms and its constant.
+    fn split_constant(expr: &Arc<Expr>) -> (Option<Arc<Expr>>, IntMachineType) {
+        match &**expr {
+            Expr::Constant(value) => (None, *value),
+            Expr::Add(left, right) => match right.constant() {
+                Some(value) => (Some(left.clone()), value),
+                None => (Some(expr.clone()), 0),
+            },
+            _ => (Some(expr.clone()), 0),
+        }
+    }
+
+    /// Split a product into its non constant factors and its constant one.
+    fn split_factor(expr: &Arc<Expr>) -> (&Arc<Expr>, IntMachineType) {
+        match &**expr {
+            Expr::Mul(terms, factor) => match factor.constant() {
+                Some(factor) => (terms, factor),
+                None => (expr, 1),
+            },
+            _ => (expr, 1),
+        }
+    }
+
+    /// Build `left + right`, constants being folded and moved to the end of the sum,
+    /// and `t * a + t * b` becoming `t * (a + b)`.
+    pub fn add(left: &Arc<Expr>, right: &Arc<Expr>) -> Arc<Expr> {
+        let (left, left_constant) = Expr::split_constant(left);
+        let (right, right_constant) = Expr::split_constant(right);
+        let constant = left_constant.wrapping_add(right_constant);
+
+        let terms = match (left, right) {
+            (None, None) => return Arc::new(Expr::Constant(constant)),
+            (Some(terms), None) | (None, Some(terms)) => terms,
+            (Some(left), Some(right)) => {
+                let (left_terms, left_factor) = Expr::split_factor(&left);
+                let (right_terms, right_factor) = Expr::split_factor(&right);
+
+                match left_terms == right_terms {
+                    true => Expr::mul(
+                        left_terms,
+                        &Arc::new(Expr::Constant(left_factor.wrapping_add(right_factor))),
+                    ),
+                    false => Arc::new(Expr::Add(left, right)),
+                }
+            }
+        };
+
+        match (terms.constant(), constant) {
+            (Some(value), _) => Arc::new(Expr::Constant(value.wrapping_add(constant))),
+            (None, 0) => terms,
+            (None, _) => Arc::new(Expr::Add(terms, Arc::new(Expr::Constant(constant)))),
+        }
+    }
+
+    /// Build `left * right`, constant factors being folded and distributed over the constant
+    /// of a sum.
+    pub fn mul(left: &Arc<Expr>, right: &Arc<Expr>) -> Arc<Expr> {
+        let (expr, factor) = match (left.constant(), right.constant()) {
+            (Some(left), Some(right)) => return Arc::new(Expr::Constant(left.wrapping_mul(right))),
+            (Some(factor), None) => (right, factor),
+            (None, Some(factor)) => (left, factor),
+            (None, None) => return Arc::new(Expr::Mul(left.clone(), right.clone())),
+        };
+
+        match (factor, &**expr) {
+            (0, _) => Arc::new(Expr::Constant(0)),
+            (1, _) => expr.clone(),
+            (_, Expr::Add(terms, constant)) if constant.constant().is_some() => {
+                let factor = Arc::new(Expr::Constant(factor));
+
+                Expr::add(&Expr::mul(terms, &factor), &Expr::mul(constant, &factor))
+            }
+            (_, Expr::Mul(terms, other_factor)) if other_factor.constant().is_some() => {
+                let factor = Arc::new(Expr::Constant(
+                    factor.wrapping_mul(other_factor.constant().unwrap_or(1)),
+                ));
+
+                Expr::mul(terms, &factor)
+            }
+            _ => Arc::new(Expr::Mul(expr.clone(), Arc::new(Expr::Constant(factor)))),
+        }
+    }
+
+    pub fn less_than(left: &Arc<Expr>, right: &Arc<Expr>) -> Arc<Expr> {
+        match (left.constant(), right.constant()) {
+            (Some(left), Some(right)) => Arc::new(Expr::Constant((left < right) as IntMachineType)),
+            _ => Arc::new(Expr::LessThan(left.clone(), right.clone())),
+        }
+    }
+
+    pub fn equals(left: &Arc<Expr>, right: &Arc<Expr>) -> Arc<Expr> {
+        match (left.constant(), right.constant()) {
+            (Some(left), Some(right)) => {
+                Arc::new(Expr::Constant((left == right) as IntMachineType))
+            }
+            _ if left == right => Arc::new(Expr::Constant(1)),
+            _ => Arc::new(Expr::Equals(left.clone(), right.clone())),
+        }
+    }
+
+    /// Every distinct subexpression, each one after its operands.
+    fn nodes(&self) -> Vec<&Expr> {
+        let mut visited = HashSet::new();
+        let mut nodes = Vec::new();
+        let mut pending = vec![(self, false)];
+
+        while let Some((expr, operands_done)) = pending.pop() {
+            if operands_done {
+                nodes.push(expr);
+                continue;
+            }
+
+            if !visited.insert(expr as *const Expr) {
+                continue;
+            }
+
+            pending.push((expr, true));
+
+            if let Some((left, right)) = expr.operands() {
+                pending.push((right, false));
+                pending.push((left, false));
+            }
+        }
+
+        nodes
+    }
+
+    /// Names of the variables the expression depends on.
+    pub fn variables(&self) -> BTreeSet<String> {
+        self.nodes()
+            .into_iter()
+            .filter_map(|expr| match expr {
+                Expr::Variable(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Compute the expression, `None` if a variable is missing or on overflow.
+    pub fn evaluate(&self, variables: &BTreeMap<String, IntMachineType>) -> Option<IntMachineType> {
+        let mut values = HashMap::<*const Expr, IntMachineType>::new();
+
+        for expr in self.nodes() {
+            let value = match expr {
+                Expr::Constant(value) => *value,
+                Expr::Variable(name) => *variables.get(name)?,
+                Expr::Add(left, right) => {
+                    values[&Arc::as_ptr(left)].checked_add(values[&Arc::as_ptr(right)])?
+                }
+                Expr::Mul(left, right) => {
+                    values[&Arc::as_ptr(left)].checked_mul(values[&Arc::as_ptr(right)])?
+                }
+                Expr::LessThan(left, right) => {
+                    (values[&Arc::as_ptr(left)] < values[&Arc::as_ptr(right)]) as IntMachineType
+                }
+                Expr::Equals(left, right) => {
+                    (values[&Arc::as_ptr(left)] == values[&Arc::as_ptr(right)]) as IntMachineType
+                }
+            };
+
+            values.insert(expr as *const Expr, value);
+        }
+
+        values.get(&(self as *const Expr)).copied()
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::LessThan(_, _) | Expr::Equals(_, _) => 0,
+            Expr::Add(_, _) => 1,
+            Expr::Mul(_, _) => 2,
+            Expr::Constant(_) | Expr::Variable(_) => 3,
+        }
+    }
+
+    /// Piece printing the expression as an operand of an operator of `precedence`.
+    fn operand(&self, precedence: u8) -> Piece<'_> {
+        let parenthesized =
+            self.precedence() < precedence || (precedence == 0 && self.precedence() == 0);
+
+        Piece::Expr(self, parenthesized)
+    }
+
+    /// Move the operands referenced nowhere else to `pending`, `leaf` replacing them.
+    fn take_unique_operands(&mut self, pending: &mut Vec<Arc<Expr>>, leaf: &mut Option<Arc<Expr>>) {
+        if let Expr::Add(left, right)
+        | Expr::Mul(left, right)
+        | Expr::LessThan(left, right)
+        | Expr::Equals(left, right) = self
+        {
+            for operand in [left, right] {
+                if Arc::strong_count(operand) == 1 {
+                    let leaf = leaf.get_or_insert_with(|| Arc::new(Expr::Constant(0)));
+
+                    pending.push(mem::replace(operand, leaf.clone()));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        let mut leaf = None;
+
+        self.take_unique_operands(&mut pending, &mut leaf);
+
+        while let Some(expr) = pending.pop() {
+            if let Ok(mut expr) = Arc::try_unwrap(expr) {
+                expr.take_unique_operands(&mut pending, &mut leaf);
+            }
+        }
+    }
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        let mut pending = vec![(self, other)];
+
+        while let Some((expr, other)) = pending.pop() {
+            if std::ptr::eq(expr, other) {
+                continue;
+            }
+
+            match (expr, other) {
+                (Expr::Constant(value), Expr::Constant(other_value)) if value == other_value => {}
+                (Expr::Variable(name), Expr::Variable(other_name)) if name == other_name => {}
+                (Expr::Add(left, right), Expr::Add(other_left, other_right))
+                | (Expr::Mul(left, right), Expr::Mul(other_left, other_right))
+                | (Expr::LessThan(left, right), Expr::LessThan(other_left, other_right))
+                | (Expr::Equals(left, right), Expr::Equals(other_left, other_right)) => {
+                    pending.push((left, other_left));
+                    pending.push((right, other_right));
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl Eq for Expr {}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut pending = vec![self];
+
+        while let Some(expr) = pending.pop() {
+            mem::discriminant(expr).hash(state);
+
+            match expr {
+                Expr::Constant(value) => value.hash(state),
+                Expr::Variable(name) => name.hash(state),
+                Expr::Add(left, right)
+                | Expr::Mul(left, right)
+                | Expr::LessThan(left, right)
+                | Expr::Equals(left, right) => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pending = vec![Piece::Expr(self, false)];
+
+        while let Some(piece) = pending.pop() {
+            let expr = match piece {
+                Piece::Expr(expr, true) => {
+                    pending.push(Piece::Text(")"));
+                    pending.push(Piece::Expr(expr, false));
+                    pending.push(Piece::Text("("));
+                    continue;
+                }
+                Piece::Expr(expr, false) => expr,
+                Piece::Text(text) => {
+                    write!(f, "{}", text)?;
+                    continue;
+                }
+                Piece::Number(value) => {
+                    write!(f, "{}", value)?;
+                    continue;
+                }
+            };
+
+            // Pieces are printed in the reverse order they are pushed.
+            let (left, operator, right) = match expr {
+                Expr::Constant(value) => {
+                    write!(f, "{}", value)?;
+                    continue;
+                }
+                Expr::Variable(name) => {
+                    write!(f, "{}", name)?;
+                    continue;
+                }
+                Expr::Add(left, right) => match right.constant() {
+                    Some(value) if value < 0 => {
+                        pending.push(Piece::Number(-i128::from(value)));
+                        pending.push(Piece::Text(" - "));
+                        pending.push(left.operand(expr.precedence()));
+                        continue;
+                    }
+                    _ => (left, " + ", right),
+                },
+                Expr::Mul(left, right) => (left, " * ", right),
+                Expr::LessThan(left, right) => (left, " < ", right),
+                Expr::Equals(left, right) => (left, " == ", right),
+            };
+
+            pending.push(right.operand(expr.precedence() + 1));
+            pending.push(Piece::Text(operator));
+            pending.push(left.operand(expr.precedence()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Word of an `IntMachine` running symbolically.
+///
+/// Words are compared, hashed and displayed through their concrete value only.
+#[derive(Debug, Clone)]
+pub struct Symbolic {
+    value: IntMachineType,
+    expr: Arc<Expr>,
+}
+
+impl Symbolic {
+    pub fn constant(value: IntMachineType) -> Self {
+        Symbolic {
+            value,
+            expr: Arc::new(Expr::Constant(value)),
+        }
+    }
+
+    /// A named unknown, `value` being the one used to run the program.
+    pub fn variable(name: &str, value: IntMachineType) -> Self {
+        Symbolic {
+            value,
+            expr: Arc::new(Expr::Variable(name.to_string())),
+        }
+    }
+
+    pub fn value(&self) -> IntMachineType {
+        self.value
+    }
+
+    pub fn expr(&self) -> &Arc<Expr> {
+        &self.expr
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.expr.constant().is_some()
+    }
+}
+
+impl PartialEq for Symbolic {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for Symbolic {}
+
+impl PartialOrd for Symbolic {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbolic {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl Hash for Symbolic {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl fmt::Display for Symbolic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl FromStr for Symbolic {
+    type Err = <IntMachineType as FromStr>::Err;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        text.parse().map(Symbolic::constant)
+    }
+}
+
+impl Symbolic {
+    fn combine(
+        &self,
+        other: &Self,
+        value: IntMachineType,
+        expr: fn(&Arc<Expr>, &Arc<Expr>) -> Arc<Expr>,
+    ) -> Self {
+        Symbolic {
+            value,
+            expr: expr(&self.expr, &other.expr),
+        }
+    }
+}
+
+impl Word for Symbolic {
+    fn zero() -> Self {
+        Symbolic::constant(0)
+    }
+
+    fn one() -> Self {
+        Symbolic::constant(1)
+    }
+
+    fn from_i64(value: i64) -> Option<Self> {
+        Some(Symbolic::constant(value))
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.value)
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        self.value.to_usize()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let value = self.value.checked_add(other.value)?;
+
+        Some(self.combine(other, value, Expr::add))
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        let value = self.value.checked_mul(other.value)?;
+
+        Some(self.combine(other, value, Expr::mul))
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self.combine(other, self.value.wrapping_add(other.value), Expr::add)
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self.combine(other, self.value.wrapping_mul(other.value), Expr::mul)
+    }
+
+    fn saturating_add(&self, other: &Self) -> Self {
+        self.combine(other, self.value.saturating_add(other.value), Expr::add)
+    }
+
+    fn saturating_mul(&self, other: &Self) -> Self {
+        self.combine(other, self.value.saturating_mul(other.value), Expr::mul)
+    }
+
+    fn less_than(&self, other: &Self) -> Self {
+        let value = (self.value < other.value) as IntMachineType;
+
+        self.combine(other, value, Expr::less_than)
+    }
+
+    fn equals(&self, other: &Self) -> Self {
+        let value = (self.value == other.value) as IntMachineType;
+
+        self.combine(other, value, Expr::equals)
+    }
+}
+
+/// Input values read as the variables `input0`, `input1`, ...
+pub struct SymbolicInput {
+    values: VecDeque<IntMachineType>,
+    count: usize,
+}
+
+impl SymbolicInput {
+    pub fn new(values: Vec<IntMachineType>) -> Self {
+        SymbolicInput {
+            values: values.into(),
+            count: 0,
+        }
+    }
+}
+
+impl Input<Symbolic> for SymbolicInput {
+    fn read(&mut self) -> Result<Option<Symbolic>, MachineError> {
+        let value = match self.values.pop_front() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let name = format!("input{}", self.count);
+
+        self.count += 1;
+        Ok(Some(Symbolic::variable(&name, value)))
+    }
+}
+
+/// Conditional jump whose condition depends on variables.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Branch {
+    pub ip: usize,
+    /// Value tested by the jump, which jumps when it is non zero for JumpIfTrue, zero for JumpIfFalse.
+    pub condition: Arc<Expr>,
+    pub taken: bool,
+}
+
+impl Branch {
+    pub fn variables(&self) -> BTreeSet<String> {
+        self.condition.variables()
+    }
+}
+
+/// Tracer recording every conditional jump depending on variables.
+#[derive(Debug, Default)]
+pub struct BranchRecorder {
+    pub branches: Vec<Branch>,
+}
+
+impl Tracer<Symbolic> for BranchRecorder {
+    fn trace(&mut self, event: &TraceEvent<Symbolic>) -> io::Result<()> {
+        let jump_if_zero = match event.instruction {
+            InstructionType::JumpIfTrue => false,
+            InstructionType::JumpIfFalse => true,
+            _ => return Ok(()),
+        };
+
+        if let Some(condition) = event.values.first().filter(|value| !value.is_constant()) {
+            self.branches.push(Branch {
+                ip: event.ip,
+                condition: condition.expr().clone(),
+                taken: (condition.value() == 0) == jump_if_zero,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use super::{BranchRecorder, Symbolic, SymbolicInput};
+    use crate::{convert_code, IntMachine, MachineReturn, Memory};
+
+    #[test]
+    pub fn test_closed_form() {
+        // ram[0] = ram[1] * 100 + ram[2] + 7, the first instruction only being patched.
+        let code = vec![1, 0, 0, 3, 2, 1, 17, 3, 1, 3, 2, 0, 1001, 0, 7, 0, 99, 100];
+        let mut machine = IntMachine::with_memory(Memory::new(convert_code(&code).unwrap()));
+
+        machine
+            .write_memory(1, Symbolic::variable("noun", 12))
+            .unwrap();
+        machine
+            .write_memory(2, Symbolic::variable("verb", 2))
+            .unwrap();
+        machine.run(false, false, Vec::<Symbolic>::new()).unwrap();
+
+        let result = machine.memory().read(0).unwrap();
+
+        assert_eq!(result.value(), 1209);
+        assert_eq!(result.expr().to_string(), "noun * 100 + verb + 7");
+
+        let variables = [("noun".to_string(), 64), ("verb".to_string(), 21)]
+            .iter()
+            .cloned()
+            .collect::<BTreeMap<String, i64>>();
+
+        assert_eq!(result.expr().evaluate(&variables), Some(6428));
+    }
+
+    #[test]
+    pub fn test_long_loops() {
+        const ITERATIONS: i64 = 200_000;
+
+        // Read x, then add it to an accumulator in a loop.
+        let mut code = vec![
+            3, 100, 1, 101, 100, 101, 1001, 102, -1, 102, 1005, 102, 2, 4, 101, 99,
+        ];
+        code.resize(100, 0);
+        code.extend(&[0, 0, ITERATIONS]);
+
+        let mut machine = IntMachine::with_memory(Memory::new(convert_code(&code).unwrap()));
+        let mut output = Vec::new();
+
+        machine
+            .run_with(false, false, &mut SymbolicInput::new(vec![3]), &mut output)
+            .unwrap();
+
+        assert_eq!(output[0].value(), 3 * ITERATIONS);
+        assert_eq!(output[0].expr().to_string(), "input0 * 200000");
+
+        // Add a new input at each iteration, the sum not simplifying.
+        code[12] = 0;
+
+        let mut machine = IntMachine::with_memory(Memory::new(convert_code(&code).unwrap()));
+        let mut output = Vec::new();
+
+        machine
+            .run_with(
+                false,
+                false,
+                &mut SymbolicInput::new((0..ITERATIONS).collect()),
+                &mut output,
+            )
+            .unwrap();
+
+        let result = output.pop().unwrap();
+        let variables = (0..ITERATIONS)
+            .map(|index| (format!("input{}", index), 1))
+            .collect::<BTreeMap<String, i64>>();
+
+        assert_eq!(result.value(), (0..ITERATIONS).sum::<i64>());
+        assert_eq!(result.expr().evaluate(&variables), Some(ITERATIONS));
+        assert_eq!(result.expr().variables().len(), ITERATIONS as usize);
+        assert!(result
+            .expr()
+            .to_string()
+            .starts_with("input0 + input1 + input2 + "));
+
+        drop(result);
+        drop(machine);
+    }
+
+    #[test]
+    pub fn test_branches() {
+        let code = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let mut machine = IntMachine::with_memory(Memory::new(convert_code(&code).unwrap()));
+        let mut output = Vec::new();
+
+        machine
+            .run_with(false, false, &mut SymbolicInput::new(vec![8]), &mut output)
+            .unwrap();
+
+        assert_eq!(output[0].value(), 1);
+        assert_eq!(output[0].expr().to_string(), "input0 == 8");
+
+        let code = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let recorder = Arc::new(Mutex::new(BranchRecorder::default()));
+        let mut machine = IntMachine::with_memory(Memory::new(convert_code(&code).unwrap()));
+
+        machine.set_tracer(Some(Box::new(recorder.clone())));
+
+        assert_eq!(
+            machine.run_with(
+                false,
+                false,
+                &mut SymbolicInput::new(vec![42]),
+                &mut Vec::new()
+            ),
+            Ok(MachineReturn::Exit(vec![Symbolic::constant(1)]))
+        );
+
+        let branches = &recorder.lock().unwrap().branches;
+
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].ip, 2);
+        assert_eq!(branches[0].condition.to_string(), "input0");
+        assert!(!branches[0].taken);
+        assert_eq!(
+            branches[0].variables().into_iter().collect::<Vec<_>>(),
+            vec!["input0"]
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Per-instruction execution tracing.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::disasm::Operand;
+use crate::instruction::InstructionType;
+use crate::word::Word;
+use crate::IntMachineType;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct MemoryRead<W = IntMachineType> {
+    pub address: usize,
+    pub value: W,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct MemoryWrite<W = IntMachineType> {
+    pub address: usize,
+    pub old_value: W,
+    pub new_value: W,
+}
+
+/// Everything an executed instruction did.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraceEvent<W = IntMachineType> {
+    pub step: u64,
+    pub ip: usize,
+    pub opcode: W,
+    pub instruction: InstructionType,
+    pub relative_base: W,
+    pub operands: Vec<Operand<W>>,
+    /// Values of the operands read by the instruction, in operand order.
+    pub values: Vec<W>,
+    pub reads: Vec<MemoryRead<W>>,
+    pub writes: Vec<MemoryWrite<W>>,
+}
+
+impl<W: Word> TraceEvent<W> {
+    pub(crate) fn new(
+        step: u64,
+        ip: usize,
+        opcode: W,
+        instruction: InstructionType,
+        relative_base: W,
+    ) -> Self {
+        TraceEvent {
+            step,
+            ip,
+            opcode,
+            instruction,
+            relative_base,
+            operands: Vec::new(),
+            values: Vec::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    fn operands_text(&self) -> Vec<String> {
+        self.operands.iter().map(Operand::to_string).collect()
+    }
+
+    /// Format the event as a single JSON object.
+    pub fn to_json(&self) -> String {
+        let operands = self
+            .operands_text()
+            .iter()
+            .map(|operand| format!("\"{}\"", operand))
+            .collect::<Vec<String>>();
+
+        let values = self
+            .values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<String>>();
+
+        let reads = self
+            .reads
+            .iter()
+            .map(|read| format!("{{\"address\":{},\"value\":{}}}", read.address, read.value))
+            .collect::<Vec<String>>();
+
+        let writes = self
+            .writes
+            .iter()
+            .map(|write| {
+                format!(
+                    "{{\"address\":{},\"old\":{},\"new\":{}}}",
+                    write.address, write.old_value, write.new_value
+                )
+            })
+            .collect::<Vec<String>>();
+
+        format!(
+            "{{\"step\":{},\"ip\":{},\"opcode\":{},\"instruction\":\"{}\",\"relative_base\":{},\"operands\":[{}],\"values\":[{}],\"reads\":[{}],\"writes\":[{}]}}",
+            self.step,
+            self.ip,
+            self.opcode,
+            self.instruction.mnemonic(),
+            self.relative_base,
+            operands.join(","),
+            values.join(","),
+            reads.join(","),
+            writes.join(",")
+        )
+    }
+
+    /// Format the event as a compact single line.
+    pub fn to_text(&self) -> String {
+        let mut result = format!(
+            "{:>6} {:>5}: {}",
+            self.step,
+            self.ip,
+            self.instruction.mnemonic()
+        );
+
+        if !self.operands.is_empty() {
+            result.push(' ');
+            result.push_str(&self.operands_text().join(", "));
+        }
+
+        for read in &self.reads {
+            result.push_str(&format!(" | [{}]={}", read.address, read.value));
+        }
+
+        for write in &self.writes {
+            result.push_str(&format!(
+                " | [{}] {} -> {}",
+                write.address, write.old_value, write.new_value
+            ));
+        }
+
+        result
+    }
+}
+
+/// Receive an event for every instruction executed by an `IntMachine`.
+pub trait Tracer<W = IntMachineType> {
+    fn trace(&mut self, event: &TraceEvent<W>) -> io::Result<()>;
+}
+
+/// Keep every event in memory.
+impl<W: Word> Tracer<W> for Vec<TraceEvent<W>> {
+    fn trace(&mut self, event: &TraceEvent<W>) -> io::Result<()> {
+        self.push(event.clone());
+        Ok(())
+    }
+}
+
+/// Share a tracer with the machine so it can still be inspected while it's attached.
+impl<W, T: Tracer<W>> Tracer<W> for Arc<Mutex<T>> {
+    fn trace(&mut self, event: &TraceEvent<W>) -> io::Result<()> {
+        match self.lock() {
+            Ok(mut tracer) => tracer.trace(event),
+            Err(_) => Err(io::Error::other("tracer mutex poisoned")),
+        }
+    }
+}
+
+/// Write one JSON object per executed instruction.
+pub struct JsonLinesTracer<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesTracer<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesTracer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, V: Word> Tracer<V> for JsonLinesTracer<W> {
+    fn trace(&mut self, event: &TraceEvent<V>) -> io::Result<()> {
+        writeln!(self.writer, "{}", event.to_json())
+    }
+}
+
+/// Write one compact text line per executed instruction.
+pub struct TextTracer<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(writer: W) -> Self {
+        TextTracer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, V: Word> Tracer<V> for TextTracer<W> {
+    fn trace(&mut self, event: &TraceEvent<V>) -> io::Result<()> {
+        writeln!(self.writer, "{}", event.to_text())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{JsonLinesTracer, MemoryRead, MemoryWrite, TextTracer, TraceEvent, Tracer};
+    use crate::disasm::Operand;
+    use crate::{InstructionType, IntMachine, MachineReturn};
+
+    fn run_traced(code: Vec<i64>, input: Vec<i64>) -> Vec<TraceEvent> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = IntMachine::new(code);
+
+        machine.set_tracer(Some(Box::new(events.clone())));
+        machine.run(false, false, input).unwrap();
+
+        let events = events.lock().unwrap();
+        events.clone()
+    }
+
+    #[test]
+    pub fn test_events() {
+        let events = run_traced(vec![1002, 4, 3, 4, 33], vec![]);
+
+        assert_eq!(
+            events,
+            vec![
+                TraceEvent {
+                    step: 0,
+                    ip: 0,
+                    opcode: 1002,
+                    instruction: InstructionType::Multiplication,
+                    relative_base: 0,
+                    operands: vec![
+                        Operand::Position(4),
+                        Operand::Immediate(3),
+                        Operand::Position(4)
+                    ],
+                    values: vec![33, 3],
+                    reads: vec![MemoryRead {
+                        address: 4,
+                        value: 33
+                    }],
+                    writes: vec![MemoryWrite {
+                        address: 4,
+                        old_value: 33,
+                        new_value: 99
+                    }],
+                },
+                TraceEvent::new(1, 4, 99, InstructionType::Exit, 0),
+            ]
+        );
+
+        let events = run_traced(vec![3, 0, 3, 0, 4, 0, 99], vec![1]);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].writes[0].new_value, 1);
+    }
+
+    #[test]
+    pub fn test_writers() {
+        let event = run_traced(vec![109, 3, 22101, 5, -3, 0, 204, 0, 99], vec![])[1].clone();
+
+        assert_eq!(
+            event.to_json(),
+            "{\"step\":1,\"ip\":2,\"opcode\":22101,\"instruction\":\"add\",\"relative_base\":3,\
+             \"operands\":[\"#5\",\"[rb-3]\",\"[rb+0]\"],\"values\":[5,109],\
+             \"reads\":[{\"address\":0,\"value\":109}],\
+             \"writes\":[{\"address\":3,\"old\":5,\"new\":114}]}"
+        );
+        assert_eq!(
+            event.to_text(),
+            "     1     2: add #5, [rb-3], [rb+0] | [0]=109 | [3] 5 -> 114"
+        );
+
+        let tracer = Arc::new(Mutex::new(TextTracer::new(Vec::new())));
+        let mut machine = IntMachine::new(vec![104, 7, 99]);
+
+        machine.set_tracer(Some(Box::new(tracer.clone())));
+        assert_eq!(
+            machine.run(false, false, vec![]),
+            Ok(MachineReturn::Exit(vec![7]))
+        );
+        machine.set_tracer(None);
+
+        let tracer = Arc::try_unwrap(tracer).ok().unwrap().into_inner().unwrap();
+
+        assert_eq!(
+            String::from_utf8(tracer.into_inner()).unwrap(),
+            "     0     0: out #7\n     1     2: hlt\n"
+        );
+
+        let mut tracer = JsonLinesTracer::new(Vec::new());
+
+        for event in run_traced(vec![104, 7, 99], vec![]) {
+            tracer.trace(&event).unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(tracer.into_inner())
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use crate::IntMachineType;
+
+/// Numeric type stored in the memory cells of an `IntMachine`.
+pub trait Word: Clone + Debug + Display + Ord + Hash + FromStr + Send + Sync + 'static {
+    fn zero() -> Self;
+    fn one() -> Self;
+
+    fn from_i64(value: i64) -> Option<Self>;
+    fn to_i64(&self) -> Option<i64>;
+    fn to_usize(&self) -> Option<usize>;
+
+    fn is_negative(&self) -> bool {
+        *self < Self::zero()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+    fn saturating_add(&self, other: &Self) -> Self;
+    fn saturating_mul(&self, other: &Self) -> Self;
+
+    /// Result of the LessThan instruction.
+    fn less_than(&self, other: &Self) -> Self {
+        match self < other {
+            true => Self::one(),
+            false => Self::zero(),
+        }
+    }
+
+    /// Result of the Equals instruction.
+    fn equals(&self, other: &Self) -> Self {
+        match self == other {
+            true => Self::one(),
+            false => Self::zero(),
+        }
+    }
+}
+
+macro_rules! impl_word {
+    ($($word:ty),*) => {
+        $(
+            impl Word for $word {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn from_i64(value: i64) -> Option<Self> {
+                    std::convert::TryFrom::try_from(value).ok()
+                }
+
+                fn to_i64(&self) -> Option<i64> {
+                    std::convert::TryFrom::try_from(*self).ok()
+                }
+
+                fn to_usize(&self) -> Option<usize> {
+                    std::convert::TryFrom::try_from(*self).ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$word>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$word>::checked_mul(*self, *other)
+                }
+
+                fn wrapping_add(&self, other: &Self) -> Self {
+                    <$word>::wrapping_add(*self, *other)
+                }
+
+                fn wrapping_mul(&self, other: &Self) -> Self {
+                    <$word>::wrapping_mul(*self, *other)
+                }
+
+                fn saturating_add(&self, other: &Self) -> Self {
+                    <$word>::saturating_add(*self, *other)
+                }
+
+                fn saturating_mul(&self, other: &Self) -> Self {
+                    <$word>::saturating_mul(*self, *other)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(i32, i64, i128);
+
+/// Convert a program to another word type, `None` if a value doesn't fit.
+pub fn convert_code<W: Word>(code: &[IntMachineType]) -> Option<Vec<W>> {
+    code.iter().map(|value| W::from_i64(*value)).collect()
+}
+
+/// Arbitrary precision words, arithmetic never overflows.
+#[cfg(feature = "bigint")]
+impl Word for num_bigint::BigInt {
+    fn zero() -> Self {
+        num_traits::Zero::zero()
+    }
+
+    fn one() -> Self {
+        num_traits::One::one()
+    }
+
+    fn from_i64(value: i64) -> Option<Self> {
+        Some(value.into())
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        num_traits::ToPrimitive::to_i64(self)
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        num_traits::ToPrimitive::to_usize(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn saturating_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn saturating_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{convert_code, Word};
+
+    #[test]
+    pub fn test_convert_code() {
+        assert_eq!(convert_code::<i32>(&[1, -2, 99]), Some(vec![1, -2, 99]));
+        assert_eq!(convert_code::<i32>(&[1, 1 << 40]), None);
+        assert_eq!(
+            convert_code::<i128>(&[i64::MIN]),
+            Some(vec![i64::MIN as i128])
+        );
+        assert_eq!(Word::to_usize(&-1i32), None);
+        assert_eq!(Word::checked_mul(&(1i128 << 100), &4), Some(1 << 102));
+        assert_eq!(Word::checked_mul(&(1i128 << 126), &4), None);
+    }
+}